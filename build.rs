@@ -0,0 +1,25 @@
+use std::env;
+use std::fs;
+use std::path::Path;
+
+const EXAMPLES_DIR: &str = "examples/2024";
+
+/// Generate a test for every example that has a sidecar `.answers` file.
+fn main() {
+    println!("cargo:rerun-if-changed={EXAMPLES_DIR}");
+    let mut names = fs::read_dir(EXAMPLES_DIR)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "txt"))
+        .filter(|path| path.with_extension("answers").exists())
+        .map(|path| path.file_stem().unwrap().to_str().unwrap().to_string())
+        .collect::<Vec<_>>();
+    names.sort();
+    let mut tests = String::new();
+    for name in names {
+        let test_name = name.replace('-', "_");
+        tests += &format!("#[test]\nfn {test_name}() {{\n    check_example({name:?});\n}}\n\n");
+    }
+    let out_dir = env::var("OUT_DIR").unwrap();
+    fs::write(Path::new(&out_dir).join("examples.rs"), tests).unwrap();
+}
@@ -1,33 +1,251 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
+use nohash_hasher::IntMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-type Input = (Vec<i32>, Vec<i32>);
+/// The lists, by column.
+type Input = Vec<Vec<i32>>;
 
 #[aoc_generator(day1)]
 fn parse(input: &str) -> Input {
-    let mut left = Vec::new();
-    let mut right = Vec::new();
-    for line in input.lines() {
-        let (x, y) = line.split_once(' ').unwrap();
-        left.push(x.trim().parse().unwrap());
-        right.push(y.trim().parse().unwrap());
+    let mut columns = Vec::<Vec<i32>>::new();
+    for (i, line) in input.lines().enumerate() {
+        let values = line
+            .split_whitespace()
+            .map(|x| x.parse().unwrap())
+            .collect::<Vec<_>>();
+        if i == 0 {
+            columns.resize(values.len(), Vec::new());
+        }
+        assert_eq!(
+            values.len(),
+            columns.len(),
+            "wrong number of columns on line {i}"
+        );
+        for (column, value) in columns.iter_mut().zip(values) {
+            column.push(value);
+        }
     }
-    (left, right)
+    columns
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Metric {
+    /// Sum of absolute differences between the sorted lists.
+    Distance,
+    /// Sum of squared differences between the sorted lists.
+    SquaredDistance,
+    /// Sum of each left value multiplied by how often it appears in the right list.
+    Similarity,
+    /// Number of pairs of lines whose left and right values are ordered differently.
+    Inversions,
+    /// Number of values in both lists, counting duplicates.
+    Intersection,
+}
+
+impl FromStr for Metric {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "distance" => Metric::Distance,
+            "squared" => Metric::SquaredDistance,
+            "similarity" => Metric::Similarity,
+            "inversions" => Metric::Inversions,
+            "intersection" => Metric::Intersection,
+            _ => return Err(format!("unknown metric: {s}")),
+        })
+    }
+}
+
+impl Display for Metric {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Metric::Distance => "distance",
+            Metric::SquaredDistance => "squared",
+            Metric::Similarity => "similarity",
+            Metric::Inversions => "inversions",
+            Metric::Intersection => "intersection",
+        })
+    }
+}
+
+struct ListComparison<'a> {
+    left: &'a [i32],
+    right: &'a [i32],
+}
+
+impl<'a> ListComparison<'a> {
+    fn new(left: &'a [i32], right: &'a [i32]) -> Self {
+        assert_eq!(left.len(), right.len(), "lists must have the same length");
+        Self { left, right }
+    }
+
+    fn metric(&self, metric: Metric) -> i64 {
+        match metric {
+            Metric::Distance => self.distance(),
+            Metric::SquaredDistance => self.squared_distance(),
+            Metric::Similarity => self.similarity(),
+            Metric::Inversions => self.inversions(),
+            Metric::Intersection => self.intersection(),
+        }
+    }
+
+    fn sorted_pairs(&self) -> impl Iterator<Item = (i64, i64)> {
+        let mut left = self.left.to_vec();
+        let mut right = self.right.to_vec();
+        left.sort_unstable();
+        right.sort_unstable();
+        left.into_iter()
+            .zip(right)
+            .map(|(x, y)| (x as i64, y as i64))
+    }
+
+    fn distance(&self) -> i64 {
+        self.sorted_pairs().map(|(x, y)| (x - y).abs()).sum()
+    }
+
+    fn squared_distance(&self) -> i64 {
+        self.sorted_pairs().map(|(x, y)| (x - y).pow(2)).sum()
+    }
+
+    fn similarity(&self) -> i64 {
+        let right_counts = counts(self.right);
+        self.left
+            .iter()
+            .map(|x| *x as i64 * right_counts.get(x).copied().unwrap_or_default() as i64)
+            .sum()
+    }
+
+    fn inversions(&self) -> i64 {
+        // Sort lines by their left value (and break ties by their right value),
+        // then count how often a right value is followed by a smaller one.
+        let mut lines = self.left.iter().zip(self.right).collect::<Vec<_>>();
+        lines.sort_unstable();
+        let mut right = lines.into_iter().map(|(_, &y)| y).collect::<Vec<_>>();
+        count_inversions(&mut right)
+    }
+
+    fn intersection(&self) -> i64 {
+        let left_counts = counts(self.left);
+        let right_counts = counts(self.right);
+        left_counts
+            .iter()
+            .map(|(x, &count)| count.min(right_counts.get(x).copied().unwrap_or_default()) as i64)
+            .sum()
+    }
+}
+
+fn counts(list: &[i32]) -> IntMap<i32, usize> {
+    let mut counts = IntMap::default();
+    for &x in list {
+        *counts.entry(x).or_default() += 1;
+    }
+    counts
+}
+
+/// Count the pairs `i < j` with `list[i] > list[j]`, using merge sort.
+fn count_inversions(list: &mut [i32]) -> i64 {
+    if list.len() <= 1 {
+        return 0;
+    }
+    let (left, right) = list.split_at_mut(list.len() / 2);
+    let mut inversions = count_inversions(left) + count_inversions(right);
+    let mut merged = Vec::with_capacity(left.len() + right.len());
+    let (mut i, mut j) = (0, 0);
+    while i < left.len() && j < right.len() {
+        if left[i] <= right[j] {
+            merged.push(left[i]);
+            i += 1;
+        } else {
+            // All remaining values in the left half are greater than this one
+            inversions += (left.len() - i) as i64;
+            merged.push(right[j]);
+            j += 1;
+        }
+    }
+    merged.extend_from_slice(&left[i..]);
+    merged.extend_from_slice(&right[j..]);
+    list.copy_from_slice(&merged);
+    inversions
+}
+
+/// Compare every pair of columns using the given metric.
+fn comparison_matrix(columns: &[Vec<i32>], metric: Metric) -> Vec<Vec<i64>> {
+    columns
+        .iter()
+        .map(|left| {
+            columns
+                .iter()
+                .map(|right| ListComparison::new(left, right).metric(metric))
+                .collect()
+        })
+        .collect()
+}
+
+fn print_matrix(columns: &[Vec<i32>], metric: Metric) {
+    let matrix = comparison_matrix(columns, metric);
+    let width = matrix
+        .iter()
+        .flatten()
+        .map(|value| value.to_string().len())
+        .max()
+        .unwrap_or_default()
+        .max(metric.to_string().len());
+    print!("{metric:>width$}");
+    for j in 0..columns.len() {
+        print!(" {j:>width$}");
+    }
+    println!();
+    for (i, row) in matrix.iter().enumerate() {
+        print!("{i:>width$}");
+        for value in row {
+            print!(" {value:>width$}");
+        }
+        println!();
+    }
+}
+
+const LEFT_COLUMN: Param<usize> = Param::new("left_column", 0);
+const RIGHT_COLUMN: Param<usize> = Param::new("right_column", 1);
+const METRIC_PART1: Param<Metric> = Param::for_part(1, "metric", Metric::Distance);
+const METRIC_PART2: Param<Metric> = Param::for_part(2, "metric", Metric::Similarity);
+const MATRIX: Param<bool> = Param::new("matrix", false);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[
+    &LEFT_COLUMN,
+    &RIGHT_COLUMN,
+    &METRIC_PART1,
+    &METRIC_PART2,
+    &MATRIX,
+];
+
+fn compare_columns(columns: &Input, metric: Metric) -> i64 {
+    if MATRIX.get() {
+        print_matrix(columns, metric);
+    }
+    selected_columns(columns).metric(metric)
+}
+
+fn selected_columns(columns: &Input) -> ListComparison<'_> {
+    let (left, right) = (LEFT_COLUMN.get(), RIGHT_COLUMN.get());
+    assert!(
+        left < columns.len() && right < columns.len(),
+        "input only has {} columns",
+        columns.len()
+    );
+    ListComparison::new(&columns[left], &columns[right])
 }
 
 #[aoc(day1, part1)]
-fn part1(input: &Input) -> i32 {
-    let (mut left, mut right) = input.clone();
-    left.sort();
-    right.sort();
-    left.iter().zip(right).map(|(x, y)| (x - y).abs()).sum()
+fn part1(columns: &Input) -> i64 {
+    compare_columns(columns, METRIC_PART1.get())
 }
 
 #[aoc(day1, part2)]
-fn part2(input: &Input) -> i32 {
-    let (left, right) = input;
-    left.iter()
-        .map(|x| x * right.iter().filter(|&y| y == x).count() as i32)
-        .sum()
+fn part2(columns: &Input) -> i64 {
+    compare_columns(columns, METRIC_PART2.get())
 }
 
 #[cfg(test)]
@@ -50,4 +268,49 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 31);
     }
+
+    #[test]
+    fn metrics_example() {
+        let columns = parse(EXAMPLE);
+        let comparison = ListComparison::new(&columns[0], &columns[1]);
+        assert_eq!(comparison.metric(Metric::Distance), 11);
+        assert_eq!(comparison.metric(Metric::SquaredDistance), 35);
+        assert_eq!(comparison.metric(Metric::Similarity), 31);
+        assert_eq!(comparison.metric(Metric::Inversions), 5);
+        assert_eq!(comparison.metric(Metric::Intersection), 4);
+    }
+
+    const EXAMPLE_COLUMNS: &str = "3\t4   1
+4\t3   2
+2\t5   3";
+
+    #[test]
+    fn parse_columns() {
+        assert_eq!(
+            parse(EXAMPLE_COLUMNS),
+            vec![vec![3, 4, 2], vec![4, 3, 5], vec![1, 2, 3]]
+        );
+    }
+
+    #[test]
+    fn matrix_example() {
+        let columns = parse(EXAMPLE_COLUMNS);
+        assert_eq!(
+            comparison_matrix(&columns, Metric::Distance),
+            vec![vec![0, 3, 3], vec![3, 0, 6], vec![3, 6, 0]]
+        );
+        assert_eq!(
+            comparison_matrix(&columns, Metric::Similarity),
+            vec![vec![9, 7, 5], vec![7, 12, 3], vec![5, 3, 6]]
+        );
+        print_matrix(&columns, Metric::Similarity);
+    }
+
+    #[test]
+    fn count_inversions_example() {
+        assert_eq!(count_inversions(&mut [1, 2, 3]), 0);
+        assert_eq!(count_inversions(&mut [3, 2, 1]), 3);
+        assert_eq!(count_inversions(&mut [2, 4, 1, 3, 5]), 3);
+        assert_eq!(count_inversions(&mut [1, 1, 1]), 0);
+    }
 }
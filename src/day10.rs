@@ -1,85 +1,298 @@
+use crate::params::{Param, Parameter};
 use crate::util::Vector2D;
 use aoc_runner_derive::{aoc, aoc_generator};
-use pathfinding::prelude::{count_paths, dfs_reach};
 use std::collections::HashMap;
+use std::fmt::Display;
+use std::num::NonZeroI32;
 
 struct HeightMap {
+    width: i32,
+    height: i32,
+    /// The height of every passable position.
     heights: HashMap<Vector2D, u32>,
 }
 
 #[aoc_generator(day10)]
 fn parse(input: &str) -> HeightMap {
+    let height = input.lines().count() as i32;
+    let width = input.lines().next().unwrap().len() as i32;
     let mut heights = HashMap::new();
     for (y, line) in input.lines().enumerate() {
         for (x, c) in line.chars().enumerate() {
-            heights.insert(Vector2D::new(x as i32, y as i32), c.to_digit(10).unwrap());
+            if c == '.' {
+                // Impassable
+                continue;
+            }
+            let height = c
+                .to_digit(10)
+                .unwrap_or_else(|| panic!("unknown char: {}", c));
+            heights.insert(Vector2D::new(x as i32, y as i32), height);
         }
     }
-    HeightMap { heights }
+    HeightMap {
+        width,
+        height,
+        heights,
+    }
+}
+
+/// Which heights a trail must start and end at, and how much it climbs with every step.
+#[derive(Debug, Copy, Clone)]
+struct ClimbRules {
+    start: u32,
+    end: u32,
+    step: i32,
 }
 
+impl ClimbRules {
+    fn is_valid_step(&self, height: u32, next_height: u32) -> bool {
+        height.checked_add_signed(self.step) == Some(next_height)
+    }
+}
+
+type Trail = Vec<Vector2D>;
+
 impl HeightMap {
-    fn trailheads(&self) -> impl Iterator<Item = Vector2D> + use<'_> {
+    fn trailheads<'a>(&'a self, rules: &'a ClimbRules) -> impl Iterator<Item = Vector2D> + 'a {
         self.heights
             .iter()
-            .filter_map(|(&pos, &height)| if height == 0 { Some(pos) } else { None })
-    }
-
-    fn trailhead_score(&self, start: Vector2D) -> usize {
-        let start_height = *self.heights.get(&start).unwrap();
-        dfs_reach((start, start_height), |&(pos, height)| {
-            self.neighbours(pos, height)
-        })
-        .filter(|&(_, height)| height == 9)
-        .count()
+            .filter_map(|(&pos, &height)| (height == rules.start).then_some(pos))
     }
 
-    fn neighbours(
-        &self,
+    fn neighbours<'a>(
+        &'a self,
         pos: Vector2D,
         height: u32,
-    ) -> impl Iterator<Item = (Vector2D, u32)> + use<'_> {
+        rules: &'a ClimbRules,
+    ) -> impl Iterator<Item = (Vector2D, u32)> + 'a {
         pos.neighbours().filter_map(move |neighbour| {
             let neighbour_height = *self.heights.get(&neighbour)?;
-            if neighbour_height == height + 1 {
+            if rules.is_valid_step(height, neighbour_height) {
                 Some((neighbour, neighbour_height))
             } else {
                 None
             }
         })
     }
+
+    /// List all distinct trails from the given trailhead.
+    fn trails(&self, start: Vector2D, rules: &ClimbRules) -> Vec<Trail> {
+        fn inner(
+            map: &HeightMap,
+            trail: &mut Trail,
+            height: u32,
+            rules: &ClimbRules,
+            result: &mut Vec<Trail>,
+        ) {
+            if height == rules.end {
+                result.push(trail.clone());
+                return;
+            }
+            let pos = *trail.last().unwrap();
+            for (next, next_height) in map.neighbours(pos, height, rules) {
+                trail.push(next);
+                inner(map, trail, next_height, rules, result);
+                trail.pop();
+            }
+        }
+
+        let mut result = Vec::new();
+        let start_height = *self.heights.get(&start).unwrap();
+        inner(self, &mut vec![start], start_height, rules, &mut result);
+        result
+    }
 }
 
-#[aoc(day10, part1)]
-fn part1(input: &HeightMap) -> usize {
-    input
-        .trailheads()
-        .map(|trailhead| input.trailhead_score(trailhead))
-        .sum()
+/// A value for every passable position on the map.
+struct Heatmap<T> {
+    width: i32,
+    height: i32,
+    values: HashMap<Vector2D, T>,
+}
+
+impl<T: Display> Heatmap<T> {
+    /// Draw the values as a grid, with `.` for impassable positions.
+    fn render(&self) -> String {
+        let cells = self.cells(".");
+        let width = cells.iter().flatten().map(String::len).max().unwrap_or(1);
+        let mut result = String::new();
+        for row in cells {
+            let row = row
+                .iter()
+                .map(|cell| format!("{cell:>width$}"))
+                .collect::<Vec<_>>();
+            result += &row.join(" ");
+            result.push('\n');
+        }
+        result
+    }
+
+    /// Export the values as CSV, with empty cells for impassable positions.
+    #[allow(unused)]
+    fn to_csv(&self) -> String {
+        let mut result = String::new();
+        for row in self.cells("") {
+            result += &row.join(",");
+            result.push('\n');
+        }
+        result
+    }
+
+    fn cells(&self, impassable: &str) -> Vec<Vec<String>> {
+        (0..self.height)
+            .map(|y| {
+                (0..self.width)
+                    .map(|x| match self.values.get(&Vector2D::new(x, y)) {
+                        Some(value) => value.to_string(),
+                        None => impassable.to_string(),
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+}
+
+/// For every position, the trails that start there.
+struct Heatmaps {
+    /// Number of distinct trail ends that can be reached.
+    scores: Heatmap<usize>,
+    /// Number of distinct trails.
+    ratings: Heatmap<u64>,
+}
+
+/// A set of trail ends, by index.
+#[derive(Debug, Clone, Default)]
+struct PeakSet(Vec<u64>);
+
+impl PeakSet {
+    fn insert(&mut self, index: usize) {
+        let (word, bit) = (index / 64, index % 64);
+        if self.0.len() <= word {
+            self.0.resize(word + 1, 0);
+        }
+        self.0[word] |= 1 << bit;
+    }
+
+    fn union_with(&mut self, other: &PeakSet) {
+        if self.0.len() < other.0.len() {
+            self.0.resize(other.0.len(), 0);
+        }
+        for (word, &other) in self.0.iter_mut().zip(&other.0) {
+            *word |= other;
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.0.iter().map(|word| word.count_ones() as usize).sum()
+    }
 }
 
 impl HeightMap {
-    fn trailhead_rating(&self, start: Vector2D) -> usize {
-        let start_height = *self.heights.get(&start).unwrap();
-        count_paths(
-            (start, start_height),
-            |&(pos, height)| self.neighbours(pos, height),
-            |&(_, height)| height == 9,
-        )
+    /// Compute the score and rating for every position at once,
+    /// visiting the positions from the end of a trail to the start.
+    fn heatmaps(&self, rules: &ClimbRules) -> Heatmaps {
+        let mut positions = self.heights.iter().collect::<Vec<_>>();
+        // Every step of a trail must be visited before the step that leads to it
+        positions.sort_by_key(|&(_, &height)| height as i64 * -rules.step.signum() as i64);
+        let mut peaks = HashMap::<Vector2D, PeakSet>::new();
+        let mut ratings = HashMap::<Vector2D, u64>::new();
+        let mut peak_count = 0;
+        for (&pos, &height) in positions {
+            let mut pos_peaks = PeakSet::default();
+            let mut rating = 0u64;
+            if height == rules.end {
+                pos_peaks.insert(peak_count);
+                peak_count += 1;
+                rating = 1;
+            } else {
+                for (next, _) in self.neighbours(pos, height, rules) {
+                    pos_peaks.union_with(&peaks[&next]);
+                    rating += ratings[&next];
+                }
+            }
+            peaks.insert(pos, pos_peaks);
+            ratings.insert(pos, rating);
+        }
+        let scores = peaks
+            .into_iter()
+            .map(|(pos, peaks)| (pos, peaks.len()))
+            .collect();
+        Heatmaps {
+            scores: Heatmap {
+                width: self.width,
+                height: self.height,
+                values: scores,
+            },
+            ratings: Heatmap {
+                width: self.width,
+                height: self.height,
+                values: ratings,
+            },
+        }
+    }
+}
+
+#[allow(unused)]
+fn print_heatmaps(map: &HeightMap, rules: &ClimbRules) {
+    let heatmaps = map.heatmaps(rules);
+    println!("scores:\n{}", heatmaps.scores.render());
+    println!("ratings:\n{}", heatmaps.ratings.render());
+}
+
+#[allow(unused)]
+fn print_trails(map: &HeightMap, rules: &ClimbRules) {
+    let mut trailheads = map.trailheads(rules).collect::<Vec<_>>();
+    trailheads.sort_by_key(|pos| (pos.y(), pos.x()));
+    for trailhead in trailheads {
+        for trail in map.trails(trailhead, rules) {
+            let steps = trail
+                .iter()
+                .map(|pos| format!("({},{})", pos.x(), pos.y()))
+                .collect::<Vec<_>>();
+            println!("{}", steps.join(" -> "));
+        }
     }
 }
 
+const START_HEIGHT: Param<u32> = Param::new("start_height", 0);
+const END_HEIGHT: Param<u32> = Param::new("end_height", 9);
+/// Never zero, otherwise trails could go around in circles forever.
+const STEP: Param<NonZeroI32> = Param::new("step", NonZeroI32::new(1).unwrap());
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&START_HEIGHT, &END_HEIGHT, &STEP];
+
+fn rules() -> ClimbRules {
+    ClimbRules {
+        start: START_HEIGHT.get(),
+        end: END_HEIGHT.get(),
+        step: STEP.get().get(),
+    }
+}
+
+#[aoc(day10, part1)]
+fn part1(input: &HeightMap) -> usize {
+    let rules = rules();
+    let scores = input.heatmaps(&rules).scores;
+    input
+        .trailheads(&rules)
+        .map(|trailhead| scores.values[&trailhead])
+        .sum()
+}
+
 #[aoc(day10, part2)]
-fn part2(input: &HeightMap) -> usize {
+fn part2(input: &HeightMap) -> u64 {
+    let rules = rules();
+    let ratings = input.heatmaps(&rules).ratings;
     input
-        .trailheads()
-        .map(|trailhead| input.trailhead_rating(trailhead))
+        .trailheads(&rules)
+        .map(|trailhead| ratings.values[&trailhead])
         .sum()
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use itertools::Itertools;
 
     const EXAMPLE: &str = include_str!("../examples/2024/day10.txt");
 
@@ -92,4 +305,141 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 81);
     }
+
+    const EXAMPLE_SCORE_2: &str = "\
+...0...
+...1...
+...2...
+6543456
+7.....7
+8.....8
+9.....9";
+
+    const EXAMPLE_SCORE_4: &str = "\
+..90..9
+...1.98
+...2..7
+6543456
+765.987
+876....
+987....";
+
+    const EXAMPLE_RATING_3: &str = "\
+.....0.
+..4321.
+..5..2.
+..6543.
+..7..4.
+..8765.
+..9....";
+
+    #[test]
+    fn impassable_examples() {
+        assert_eq!(part1(&parse(EXAMPLE_SCORE_2)), 2);
+        assert_eq!(part1(&parse(EXAMPLE_SCORE_4)), 4);
+        assert_eq!(part2(&parse(EXAMPLE_RATING_3)), 3);
+    }
+
+    #[test]
+    fn trails_example() {
+        let map = parse(EXAMPLE_RATING_3);
+        let rules = rules();
+        let trails = map.trails(Vector2D::new(5, 0), &rules);
+        assert_eq!(trails.len(), 3);
+        assert!(trails.iter().all(|trail| trail.len() == 10));
+        assert_eq!(
+            trails[0],
+            [
+                (5, 0),
+                (5, 1),
+                (4, 1),
+                (3, 1),
+                (2, 1),
+                (2, 2),
+                (2, 3),
+                (2, 4),
+                (2, 5),
+                (2, 6)
+            ]
+            .map(|(x, y)| Vector2D::new(x, y))
+        );
+        let map = parse(EXAMPLE);
+        let total = map
+            .trailheads(&rules)
+            .map(|trailhead| map.trails(trailhead, &rules).len())
+            .sum::<usize>();
+        assert_eq!(total, 81);
+        print_trails(&parse(EXAMPLE_RATING_3), &rules);
+    }
+
+    #[test]
+    fn heatmaps_against_trails() {
+        let map = parse(EXAMPLE);
+        let rules = rules();
+        let heatmaps = map.heatmaps(&rules);
+        for &pos in map.heights.keys() {
+            let trails = map.trails(pos, &rules);
+            let ends = trails.iter().map(|trail| *trail.last().unwrap()).unique();
+            assert_eq!(heatmaps.scores.values[&pos], ends.count());
+            assert_eq!(heatmaps.ratings.values[&pos], trails.len() as u64);
+        }
+    }
+
+    #[test]
+    fn render_heatmaps() {
+        let map = parse(EXAMPLE_SCORE_2);
+        let heatmaps = map.heatmaps(&rules());
+        assert_eq!(
+            heatmaps.scores.render(),
+            "\
+. . . 2 . . .
+. . . 2 . . .
+. . . 2 . . .
+1 1 1 2 1 1 1
+1 . . . . . 1
+1 . . . . . 1
+1 . . . . . 1
+"
+        );
+        assert_eq!(
+            heatmaps.ratings.to_csv(),
+            "\
+,,,2,,,
+,,,2,,,
+,,,2,,,
+1,1,1,2,1,1,1
+1,,,,,,1
+1,,,,,,1
+1,,,,,,1
+"
+        );
+        print_heatmaps(&parse(EXAMPLE), &rules());
+    }
+
+    #[test]
+    fn descending_rules() {
+        let map = parse(EXAMPLE_SCORE_4);
+        let rules = ClimbRules {
+            start: 9,
+            end: 0,
+            step: -1,
+        };
+        let scores = map.heatmaps(&rules).scores;
+        let score = map
+            .trailheads(&rules)
+            .map(|trailhead| scores.values[&trailhead])
+            .sum::<usize>();
+        // Every peak reaches the only trailhead
+        assert_eq!(score, 4);
+        let rules = ClimbRules {
+            start: 3,
+            end: 5,
+            step: 1,
+        };
+        let trails = map
+            .trailheads(&rules)
+            .flat_map(|trailhead| map.trails(trailhead, &rules))
+            .count();
+        assert_eq!(trails, 3);
+    }
 }
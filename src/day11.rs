@@ -1,118 +1,432 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
-use itertools::Either;
 use nohash_hasher::IntMap;
+use num_bigint::BigUint;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::path::PathBuf;
+use std::str::FromStr;
 
 #[aoc_generator(day11)]
 fn parse(input: &str) -> Vec<u64> {
     input.split(' ').map(|x| x.parse().unwrap()).collect()
 }
 
-fn blink_single(stone: u64) -> Either<u64, (u64, u64)> {
-    if stone == 0 {
-        Either::Left(1)
-    } else if count_digits(stone) % 2 == 0 {
-        let divisor = 10u64.pow(count_digits(stone) / 2);
-        Either::Right((stone / divisor, stone % divisor))
-    } else {
-        Either::Left(stone * 2024)
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Condition {
+    Equals(u64),
+    DigitsDivisibleBy(u32),
+    Always,
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Action {
+    Set(u64),
+    /// Split the digits into this many stones of equal length.
+    Split(u32),
+    Multiply(u64),
+    Add(u64),
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Rule {
+    condition: Condition,
+    action: Action,
+}
+
+/// How stones change when blinking.
+/// The first rule whose condition matches is applied, stones that match no rule stay the same.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct RuleSet {
+    base: u64,
+    rules: Vec<Rule>,
+}
+
+/// The rules from the puzzle description.
+const DEFAULT_RULES: &str = "\
+base 10
+equals 0: set 1
+digits divisible by 2: split 2
+always: multiply 2024";
+
+impl FromStr for RuleSet {
+    type Err = String;
+
+    /// Parse rules written one per line as `<condition>: <action>`,
+    /// optionally preceded by `base <n>`. Lines starting with `#` are ignored.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        fn number<T: FromStr>(s: &str) -> Result<T, String> {
+            s.trim().parse().map_err(|_| format!("invalid number: {s}"))
+        }
+
+        let mut base = 10;
+        let mut rules = Vec::new();
+        for line in s.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if let Some(value) = line.strip_prefix("base ") {
+                base = number(value)?;
+                if base < 2 {
+                    return Err(format!("base must be at least 2: {line}"));
+                }
+                continue;
+            }
+            let (condition, action) = line
+                .split_once(':')
+                .ok_or_else(|| format!("expected <condition>: <action>: {line}"))?;
+            let condition = match condition.trim() {
+                "always" => Condition::Always,
+                condition => {
+                    if let Some(value) = condition.strip_prefix("equals ") {
+                        Condition::Equals(number(value)?)
+                    } else if let Some(value) = condition.strip_prefix("digits divisible by ") {
+                        Condition::DigitsDivisibleBy(number(value)?)
+                    } else {
+                        return Err(format!("unknown condition: {condition}"));
+                    }
+                }
+            };
+            let action = match action.trim().split_once(' ') {
+                Some(("set", value)) => Action::Set(number(value)?),
+                Some(("split", value)) => Action::Split(number(value)?),
+                Some(("multiply", value)) => Action::Multiply(number(value)?),
+                Some(("add", value)) => Action::Add(number(value)?),
+                _ => return Err(format!("unknown action: {}", action.trim())),
+            };
+            match (condition, action) {
+                (Condition::DigitsDivisibleBy(0), _) | (_, Action::Split(0)) => {
+                    return Err(format!("cannot divide by zero: {line}"));
+                }
+                (_, Action::Split(1)) => {
+                    return Err(format!("must split into at least 2 stones: {line}"));
+                }
+                (Condition::DigitsDivisibleBy(parts), Action::Split(split))
+                    if parts % split == 0 => {}
+                (_, Action::Split(_)) => {
+                    return Err(format!(
+                        "can only split stones whose digits are divisible by the same number: {line}"
+                    ));
+                }
+                _ => {}
+            }
+            rules.push(Rule { condition, action });
+        }
+        Ok(RuleSet { base, rules })
     }
 }
 
-#[allow(unused)]
-fn blink(stones: &mut Vec<u64>) {
-    let mut i = 0;
-    while i < stones.len() {
-        match blink_single(stones[i]) {
-            Either::Left(stone) => {
-                stones[i] = stone;
-                i += 1
+impl RuleSet {
+    fn count_digits(&self, stone: u64) -> u32 {
+        stone.checked_ilog(self.base).unwrap_or(0) + 1
+    }
+
+    /// Blink once, passing every resulting stone to `emit` (from left to right).
+    fn blink_single(&self, stone: u64, mut emit: impl FnMut(u64)) -> Result<(), StoneOverflow> {
+        let Some(rule) = self.rules.iter().find(|rule| match rule.condition {
+            Condition::Equals(value) => stone == value,
+            Condition::DigitsDivisibleBy(k) => self.count_digits(stone) % k == 0,
+            Condition::Always => true,
+        }) else {
+            emit(stone);
+            return Ok(());
+        };
+        match rule.action {
+            Action::Set(value) => emit(value),
+            Action::Split(parts) => {
+                // With at least 2 parts, every power of the base is at most the stone itself
+                let part_digits = self.count_digits(stone) / parts;
+                let part_size = self.base.pow(part_digits);
+                // Peel off parts from the left
+                let mut divisor = self.base.pow(part_digits * (parts - 1));
+                let mut rest = stone;
+                for _ in 0..parts {
+                    emit(rest / divisor);
+                    rest %= divisor;
+                    divisor /= part_size;
+                }
             }
-            Either::Right((left, right)) => {
-                stones[i] = left;
-                stones.insert(i + 1, right);
-                i += 2
+            Action::Multiply(factor) => emit(
+                stone
+                    .checked_mul(factor)
+                    .ok_or(StoneOverflow { stone, rule: *rule })?,
+            ),
+            Action::Add(value) => emit(
+                stone
+                    .checked_add(value)
+                    .ok_or(StoneOverflow { stone, rule: *rule })?,
+            ),
+        }
+        Ok(())
+    }
+}
+
+/// A stone became too large to engrave (i.e. it doesn't fit in a `u64`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct StoneOverflow {
+    stone: u64,
+    rule: Rule,
+}
+
+impl Display for StoneOverflow {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let stone = self.stone;
+        match self.rule.action {
+            Action::Multiply(factor) => {
+                write!(f, "stone {stone} overflows when multiplied by {factor}")
+            }
+            Action::Add(value) => write!(f, "stone {stone} overflows when adding {value}"),
+            action => write!(f, "stone {stone} overflows when applying {action:?}"),
+        }
+    }
+}
+
+impl Error for StoneOverflow {}
+
+#[allow(unused)]
+fn blink(stones: &mut Vec<u64>, rules: &RuleSet) -> Result<(), StoneOverflow> {
+    let mut new_stones = Vec::with_capacity(stones.len() * 2);
+    for &stone in stones.iter() {
+        rules.blink_single(stone, |stone| new_stones.push(stone))?;
+    }
+    *stones = new_stones;
+    Ok(())
+}
+
+fn default_rules() -> RuleSet {
+    DEFAULT_RULES.parse().unwrap()
+}
+
+/// Where to find the rules: the ones from the puzzle, or a file with a rule spec.
+#[derive(Debug, Clone, Eq, PartialEq)]
+enum RulesSource {
+    Default,
+    File(PathBuf),
+}
+
+impl FromStr for RulesSource {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err("missing rules file".to_string()),
+            "default" => Ok(RulesSource::Default),
+            path => Ok(RulesSource::File(PathBuf::from(path))),
+        }
+    }
+}
+
+impl Display for RulesSource {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RulesSource::Default => f.write_str("default"),
+            RulesSource::File(path) => write!(f, "{}", path.display()),
+        }
+    }
+}
+
+impl RulesSource {
+    fn load(&self) -> Result<RuleSet, String> {
+        match self {
+            RulesSource::Default => Ok(default_rules()),
+            RulesSource::File(path) => {
+                let spec = fs::read_to_string(path)
+                    .map_err(|e| format!("failed to read {}: {e}", path.display()))?;
+                spec.parse()
+                    .map_err(|e| format!("invalid rules in {}: {e}", path.display()))
             }
         }
     }
 }
 
-fn count_digits(x: u64) -> u32 {
-    x.ilog10() + 1
+const BLINKS_PART1: Param<usize> = Param::for_part(1, "blinks", 25);
+const BLINKS_PART2: Param<usize> = Param::for_part(2, "blinks", 75);
+const RULES: Param<RulesSource> = Param::new("rules", RulesSource::Default);
+
+const COUNTING: Param<CountingStrategy> = Param::new("counting", CountingStrategy::AllStones);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&BLINKS_PART1, &BLINKS_PART2, &RULES, &COUNTING];
+
+/// How to count the stones after many blinks.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CountingStrategy {
+    /// Blink all stones together, counting the stones with every engraving.
+    AllStones,
+    /// Count how many stones every stone becomes, remembering the count for every
+    /// stone and number of blinks. Slower, but answers queries for single stones.
+    PerStone,
+}
+
+impl FromStr for CountingStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "all-stones" => CountingStrategy::AllStones,
+            "per-stone" => CountingStrategy::PerStone,
+            _ => return Err(format!("unknown counting strategy: {s}")),
+        })
+    }
+}
+
+impl Display for CountingStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CountingStrategy::AllStones => "all-stones",
+            CountingStrategy::PerStone => "per-stone",
+        })
+    }
+}
+
+fn count_stones(
+    stones: &Vec<u64>,
+    times: usize,
+    rules: &RuleSet,
+    strategy: CountingStrategy,
+) -> Result<BigUint, StoneOverflow> {
+    match strategy {
+        CountingStrategy::AllStones => blink_counts_times(stones, times, rules),
+        CountingStrategy::PerStone => {
+            let mut counter = StoneCounter::new(rules);
+            stones
+                .iter()
+                .map(|&stone| counter.count(stone, times))
+                .sum()
+        }
+    }
 }
 
 #[aoc(day11, part1)]
-fn part1(stones: &Vec<u64>) -> usize {
-    blink_counts_times(stones, 25)
+fn part1(stones: &Vec<u64>) -> Result<BigUint, Box<dyn Error>> {
+    let rules = RULES.get().load()?;
+    Ok(count_stones(
+        stones,
+        BLINKS_PART1.get(),
+        &rules,
+        COUNTING.get(),
+    )?)
 }
 
-type StoneCounts = IntMap<u64, usize>;
+/// The number of stones with every engraving.
+/// Counts are unbounded, since they grow exponentially with the number of blinks.
+type StoneCounts = IntMap<u64, BigUint>;
 
 fn to_counts(stones: &Vec<u64>) -> StoneCounts {
     let mut counts = StoneCounts::default();
     for &stone in stones {
-        *counts.entry(stone).or_default() += 1;
+        *counts.entry(stone).or_default() += 1u32;
     }
     counts
 }
 
-fn blink_counts(stones: StoneCounts) -> StoneCounts {
+fn blink_counts(stones: &StoneCounts, rules: &RuleSet) -> Result<StoneCounts, StoneOverflow> {
     let mut new_counts = StoneCounts::default();
     new_counts.reserve(stones.len());
-    for (stone, count) in stones {
-        match blink_single(stone) {
-            Either::Left(stone) => {
-                *new_counts.entry(stone).or_default() += count;
-            }
-            Either::Right((left, right)) => {
-                *new_counts.entry(left).or_default() += count;
-                *new_counts.entry(right).or_default() += count;
-            }
-        }
+    for (&stone, count) in stones {
+        rules.blink_single(stone, |stone| {
+            *new_counts.entry(stone).or_default() += count;
+        })?;
     }
-    new_counts
+    Ok(new_counts)
 }
 
-fn blink_counts_times(stones: &Vec<u64>, times: usize) -> usize {
+fn blink_counts_times(
+    stones: &Vec<u64>,
+    times: usize,
+    rules: &RuleSet,
+) -> Result<BigUint, StoneOverflow> {
     let mut counts = to_counts(stones);
     for _ in 1..=times {
-        counts = blink_counts(counts);
+        counts = blink_counts(&counts, rules)?;
+    }
+    Ok(counts.values().sum())
+}
+
+/// Counts how many stones a single stone becomes,
+/// remembering the result for every stone and number of blinks.
+struct StoneCounter<'a> {
+    rules: &'a RuleSet,
+    /// The number of stones that every stone becomes, by number of blinks.
+    cache: Vec<IntMap<u64, BigUint>>,
+}
+
+impl<'a> StoneCounter<'a> {
+    fn new(rules: &'a RuleSet) -> Self {
+        Self {
+            rules,
+            cache: Vec::new(),
+        }
+    }
+
+    /// The number of stones after blinking `depth` times, starting from a single `stone`.
+    fn count(&mut self, stone: u64, depth: usize) -> Result<BigUint, StoneOverflow> {
+        if depth == 0 {
+            return Ok(BigUint::from(1u32));
+        }
+        if self.cache.len() < depth {
+            self.cache.resize_with(depth, IntMap::default);
+        }
+        if let Some(count) = self.cache[depth - 1].get(&stone) {
+            return Ok(count.clone());
+        }
+        let mut children = Vec::with_capacity(2);
+        self.rules
+            .blink_single(stone, |child| children.push(child))?;
+        let mut count = BigUint::default();
+        for child in children {
+            count += self.count(child, depth - 1)?;
+        }
+        self.cache[depth - 1].insert(stone, count.clone());
+        Ok(count)
     }
-    counts.values().sum()
 }
 
 #[aoc(day11, part2)]
-fn part2(stones: &Vec<u64>) -> usize {
-    blink_counts_times(stones, 75)
+fn part2(stones: &Vec<u64>) -> Result<BigUint, Box<dyn Error>> {
+    let rules = RULES.get().load()?;
+    Ok(count_stones(
+        stones,
+        BLINKS_PART2.get(),
+        &rules,
+        COUNTING.get(),
+    )?)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::params;
+    use num_traits::ToPrimitive;
+    use std::collections::HashMap;
+    use std::time::Instant;
 
     #[test]
     fn part1_example1() {
+        let rules = default_rules();
         let mut stones = parse("0 1 10 99 999");
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(stones, vec![1, 2024, 1, 0, 9, 9, 2021976]);
     }
 
     #[test]
     fn part1_example2() {
+        let rules = default_rules();
         let mut stones = parse("125 17");
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(stones, vec![253000, 1, 7]);
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(stones, vec![253, 0, 2024, 14168]);
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(stones, vec![512072, 1, 20, 24, 28676032]);
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(stones, vec![512, 72, 2024, 2, 0, 2, 4, 2867, 6032]);
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(
             stones,
             vec![1036288, 7, 2, 20, 24, 4048, 1, 4048, 8096, 28, 67, 60, 32]
         );
-        blink(&mut stones);
+        blink(&mut stones, &rules).unwrap();
         assert_eq!(
             stones,
             vec![
@@ -125,12 +439,182 @@ mod tests {
     #[test]
     fn part2_example1() {
         let stones = parse("0 1 10 99 999");
-        assert_eq!(blink_counts_times(&stones, 1), 7);
+        assert_eq!(
+            blink_counts_times(&stones, 1, &default_rules()).unwrap(),
+            BigUint::from(7u32)
+        );
     }
 
     #[test]
     fn part2_example2() {
         let stones = parse("125 17");
-        assert_eq!(blink_counts_times(&stones, 6), 22);
+        assert_eq!(
+            blink_counts_times(&stones, 6, &default_rules()).unwrap(),
+            BigUint::from(22u32)
+        );
+    }
+
+    #[test]
+    fn parse_rules() {
+        let rules = default_rules();
+        assert_eq!(
+            rules,
+            RuleSet {
+                base: 10,
+                rules: vec![
+                    Rule {
+                        condition: Condition::Equals(0),
+                        action: Action::Set(1)
+                    },
+                    Rule {
+                        condition: Condition::DigitsDivisibleBy(2),
+                        action: Action::Split(2)
+                    },
+                    Rule {
+                        condition: Condition::Always,
+                        action: Action::Multiply(2024)
+                    },
+                ]
+            }
+        );
+        assert!("base 1".parse::<RuleSet>().is_err());
+        assert!("equals 0 set 1".parse::<RuleSet>().is_err());
+        assert!("sometimes: set 1".parse::<RuleSet>().is_err());
+        assert!("always: split 2".parse::<RuleSet>().is_err());
+        assert!("digits divisible by 2: split 3".parse::<RuleSet>().is_err());
+        assert!("always: divide 2".parse::<RuleSet>().is_err());
+        assert_eq!(
+            "digits divisible by 1: split 1".parse::<RuleSet>(),
+            Err("must split into at least 2 stones: digits divisible by 1: split 1".to_string())
+        );
+        // Large bases don't overflow when splitting
+        let rules = "base 4294967296\ndigits divisible by 2: split 2"
+            .parse::<RuleSet>()
+            .unwrap();
+        let mut stones = vec![u64::MAX, 1 << 32];
+        blink(&mut stones, &rules).unwrap();
+        assert_eq!(stones, vec![u32::MAX as u64, u32::MAX as u64, 1, 0]);
+    }
+
+    #[test]
+    fn custom_rules() {
+        let rules = "\
+# Split into three parts, in binary
+base 2
+digits divisible by 3: split 3
+equals 1: add 4
+always: multiply 3"
+            .parse::<RuleSet>()
+            .unwrap();
+        let mut stones = vec![0b101110, 1, 0b11];
+        blink(&mut stones, &rules).unwrap();
+        assert_eq!(stones, vec![0b10, 0b11, 0b10, 0b101, 0b1001]);
+        blink(&mut stones, &rules).unwrap();
+        assert_eq!(stones, vec![0b110, 0b1001, 0b110, 1, 0, 1, 0b11011]);
+        assert_eq!(
+            blink_counts_times(&vec![0b101110, 1, 0b11], 2, &rules).unwrap(),
+            BigUint::from(7u32)
+        );
+        let rules = "digits divisible by 4: split 2".parse::<RuleSet>().unwrap();
+        let mut stones = vec![123, 1234, 100005];
+        blink(&mut stones, &rules).unwrap();
+        assert_eq!(stones, vec![123, 12, 34, 100005]);
+    }
+
+    #[test]
+    fn count_memoized() {
+        let rules = default_rules();
+        let mut counter = StoneCounter::new(&rules);
+        assert_eq!(counter.count(125, 6).unwrap(), BigUint::from(7u32));
+        // Child stones are remembered too
+        assert_eq!(counter.cache[4][&253000], BigUint::from(7u32));
+        assert_eq!(counter.count(17, 6).unwrap(), BigUint::from(15u32));
+        assert_eq!(counter.count(125, 0).unwrap(), BigUint::from(1u32));
+        assert_eq!(
+            counter.count(125, 25).unwrap(),
+            blink_counts_times(&vec![125], 25, &rules).unwrap()
+        );
+        // Far beyond what fits in a u128
+        let count = counter.count(125, 500).unwrap();
+        assert_eq!(count.to_u128(), None);
+        assert_eq!(count, blink_counts_times(&vec![125], 500, &rules).unwrap());
+        assert_eq!(
+            count.to_string(),
+            "3249332808150955115891207801561140232045379661735751935606671476139995928900397442230351115"
+        );
+    }
+
+    #[test]
+    fn counting_strategies() {
+        let stones = parse("125 17");
+        let overrides = HashMap::from([("counting".to_string(), "per-stone".to_string())]);
+        params::with_overrides(overrides, || {
+            assert_eq!(part1(&stones).unwrap(), BigUint::from(55312u32));
+            assert_eq!(part2(&stones).unwrap(), BigUint::from(65601038650482u64));
+        });
+        assert_eq!("per-stone".parse(), Ok(CountingStrategy::PerStone));
+        assert!("each".parse::<CountingStrategy>().is_err());
+    }
+
+    #[test]
+    fn stone_overflow() {
+        let rules = "always: multiply 1000000".parse::<RuleSet>().unwrap();
+        let error = blink_counts_times(&vec![7], 4, &rules).unwrap_err();
+        assert_eq!(
+            error,
+            StoneOverflow {
+                stone: 7_000_000_000_000_000_000,
+                rule: rules.rules[0]
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "stone 7000000000000000000 overflows when multiplied by 1000000"
+        );
+    }
+
+    #[test]
+    fn rules_from_file() {
+        let path = std::env::temp_dir().join("aoc-2024-day11-rules.txt");
+        fs::write(&path, "# Doubling stones\nalways: multiply 2\n").unwrap();
+        let overrides = HashMap::from([
+            ("rules".to_string(), path.display().to_string()),
+            ("blinks".to_string(), "3".to_string()),
+        ]);
+        let stones = parse("1 2");
+        params::with_overrides(overrides.clone(), || {
+            assert_eq!(part1(&stones).unwrap(), BigUint::from(2u32));
+            assert_eq!(part2(&stones).unwrap(), BigUint::from(2u32));
+        });
+        fs::write(&path, "always: divide 2").unwrap();
+        params::with_overrides(overrides.clone(), || {
+            let error = part1(&stones).unwrap_err().to_string();
+            assert!(error.starts_with("invalid rules in "), "{error}");
+        });
+        fs::remove_file(&path).unwrap();
+        params::with_overrides(overrides, || {
+            let error = part2(&stones).unwrap_err().to_string();
+            assert!(error.starts_with("failed to read "), "{error}");
+        });
+    }
+
+    /// Compare counting all stones at once against counting every stone separately:
+    /// `cargo test --release day11::tests::bench_counts -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_counts() {
+        let stones = match crate::input::load_or_skip(11) {
+            Some(input) => parse(input.trim_end()),
+            None => parse("4022724 951333 0 21633 5857 97 702 6"),
+        };
+        let rules = default_rules();
+        let start = Instant::now();
+        let all = blink_counts_times(&stones, 75, &rules).unwrap();
+        let all_time = start.elapsed();
+        let start = Instant::now();
+        let each = count_stones(&stones, 75, &rules, CountingStrategy::PerStone).unwrap();
+        let each_time = start.elapsed();
+        assert_eq!(all, each);
+        println!("all stones: {all_time:?}, per stone: {each_time:?}");
     }
 }
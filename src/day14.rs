@@ -1,3 +1,4 @@
+use crate::params::{Param, Parameter};
 use crate::util::Vector2D;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
@@ -25,15 +26,21 @@ fn parse(input: &str) -> Vec<Robot> {
         .collect()
 }
 
+const WIDTH: Param<i32> = Param::new("width", 101);
+const HEIGHT: Param<i32> = Param::new("height", 103);
+const SECONDS: Param<usize> = Param::new("seconds", 100);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&WIDTH, &HEIGHT, &SECONDS];
+
 #[aoc(day14, part1)]
 fn part1(input: &[Robot]) -> usize {
-    solve_part1(input, 101, 103)
+    solve_part1(input, WIDTH.get(), HEIGHT.get())
 }
 
 fn solve_part1(robots: &[Robot], width: i32, height: i32) -> usize {
     let mut robots = robots.to_vec();
     let bounds = Vector2D::new(width, height);
-    for _ in 0..100 {
+    for _ in 0..SECONDS.get() {
         simulate(&mut robots, bounds);
     }
     safety_score(&robots, width, height)
@@ -68,7 +75,7 @@ fn safety_score(robots: &[Robot], width: i32, height: i32) -> usize {
 #[aoc(day14, part2)]
 fn part2(input: &[Robot]) -> usize {
     let mut robots = input.to_vec();
-    let bounds = Vector2D::new(101, 103);
+    let bounds = Vector2D::new(WIDTH.get(), HEIGHT.get());
     let mut i = 0;
     loop {
         simulate(&mut robots, bounds);
@@ -1,3 +1,4 @@
+use crate::params::{Param, Parameter};
 use crate::util::{Direction, Vector2D};
 use aoc_runner_derive::{aoc, aoc_generator};
 use pathfinding::prelude::dijkstra;
@@ -45,9 +46,14 @@ fn find_path(bytes: &[Vector2D], size: i32) -> Option<usize> {
     Some(cost)
 }
 
+const GRID_SIZE: Param<i32> = Param::new("grid_size", 70);
+const BYTES: Param<usize> = Param::new("bytes", 1024);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&GRID_SIZE, &BYTES];
+
 #[aoc(day18, part1)]
 fn part1(input: &Input) -> usize {
-    find_path(&input[0..1024], 70).expect("no solution found")
+    find_path(&input[0..BYTES.get()], GRID_SIZE.get()).expect("no solution found")
 }
 
 fn find_first_blocker(bytes: &[Vector2D], size: i32) -> Option<Vector2D> {
@@ -61,7 +67,7 @@ fn find_first_blocker(bytes: &[Vector2D], size: i32) -> Option<Vector2D> {
 
 #[aoc(day18, part2)]
 fn part2(input: &Input) -> String {
-    let blocker = find_first_blocker(input, 70).expect("no blocker found");
+    let blocker = find_first_blocker(input, GRID_SIZE.get()).expect("no blocker found");
     format!("{},{}", blocker.x(), blocker.y())
 }
 
@@ -1,5 +1,7 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
+use std::fmt::{Display, Formatter};
 
 type Input = Vec<Vec<i32>>;
 
@@ -11,39 +13,163 @@ fn parse(input: &str) -> Input {
         .collect()
 }
 
-fn is_safe<'a>(report: impl Iterator<Item = &'a i32>) -> bool {
-    let mut increasing = true;
-    let mut decreasing = true;
-    let mut valid_distance = true;
-    for (&left, &right) in report.tuple_windows() {
-        increasing = increasing && left < right;
-        decreasing = decreasing && left > right;
-        valid_distance = valid_distance && (1..=3).contains(&(left - right).abs());
+#[derive(Debug, Copy, Clone)]
+struct Rules {
+    /// Minimum difference between two adjacent levels.
+    min_step: i32,
+    /// Maximum difference between two adjacent levels.
+    max_step: i32,
+    /// Maximum number of levels that can be removed to make a report safe.
+    tolerance: usize,
+}
+
+impl Rules {
+    fn is_valid_step(&self, left: i32, right: i32, direction: i32) -> bool {
+        (self.min_step..=self.max_step).contains(&((right - left) * direction))
     }
-    (increasing || decreasing) && valid_distance
 }
 
-#[aoc(day2, part1)]
-fn part1(input: &Input) -> usize {
-    input.iter().filter(|report| is_safe(report.iter())).count()
+/// Why a report is (un)safe.
+/// The index is that of the first level which breaks the rules.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Verdict {
+    Safe,
+    MixedDirection(usize),
+    StepTooLarge(usize),
+    StepTooSmall(usize),
+    StepZero(usize),
+}
+
+impl Display for Verdict {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Verdict::Safe => write!(f, "safe"),
+            Verdict::MixedDirection(i) => write!(f, "mixed direction at {i}"),
+            Verdict::StepTooLarge(i) => write!(f, "step too large at {i}"),
+            Verdict::StepTooSmall(i) => write!(f, "step too small at {i}"),
+            Verdict::StepZero(i) => write!(f, "step zero at {i}"),
+        }
+    }
+}
+
+fn diagnose(report: &[i32], rules: &Rules) -> Verdict {
+    let mut direction = None;
+    for (i, (&left, &right)) in report.iter().tuple_windows().enumerate() {
+        let step = right - left;
+        if step == 0 && rules.min_step > 0 {
+            return Verdict::StepZero(i + 1);
+        }
+        if step != 0 && *direction.get_or_insert(step.signum()) != step.signum() {
+            return Verdict::MixedDirection(i + 1);
+        }
+        if step.abs() < rules.min_step {
+            return Verdict::StepTooSmall(i + 1);
+        }
+        if step.abs() > rules.max_step {
+            return Verdict::StepTooLarge(i + 1);
+        }
+    }
+    Verdict::Safe
 }
 
-fn is_safe_part2(report: &[i32]) -> bool {
-    if is_safe(report.iter()) {
-        // Safe without skipping any level
-        return true;
+/// Find the fewest levels to remove to make the report safe,
+/// or `None` if more levels than tolerated would need to be removed.
+fn dampen(report: &[i32], rules: &Rules) -> Option<Vec<usize>> {
+    [1, -1]
+        .into_iter()
+        .filter_map(|direction| dampen_in_direction(report, rules, direction))
+        .min_by_key(|removed| removed.len())
+}
+
+fn dampen_in_direction(report: &[i32], rules: &Rules, direction: i32) -> Option<Vec<usize>> {
+    let n = report.len();
+    let k = rules.tolerance;
+    // For every level, the fewest removals needed to make the report safe up to that level
+    // while keeping that level, along with the previous level that is kept.
+    let mut removals = vec![usize::MAX; n];
+    let mut previous = vec![None; n];
+    for i in 0..n {
+        if i <= k {
+            // Remove all levels before this one
+            removals[i] = i;
+        }
+        // Only look back as far as we can remove levels in between
+        for j in i.saturating_sub(k + 1)..i {
+            let candidate = removals[j].saturating_add(i - j - 1);
+            if candidate < removals[i] && rules.is_valid_step(report[j], report[i], direction) {
+                removals[i] = candidate;
+                previous[i] = Some(j);
+            }
+        }
+    }
+    // Remove all levels after the last kept one
+    let last = (n.saturating_sub(k + 1)..n)
+        .filter(|&i| removals[i] != usize::MAX)
+        .min_by_key(|&i| removals[i] + (n - 1 - i))?;
+    if removals[last] + (n - 1 - last) > k {
+        return None;
+    }
+    let mut kept = vec![false; n];
+    let mut current = Some(last);
+    while let Some(i) = current {
+        kept[i] = true;
+        current = previous[i];
+    }
+    Some((0..n).filter(|&i| !kept[i]).collect())
+}
+
+fn is_safe(report: &[i32], rules: &Rules) -> bool {
+    report.is_empty() || dampen(report, rules).is_some()
+}
+
+const MIN_STEP: Param<i32> = Param::new("min_step", 1);
+const MAX_STEP: Param<i32> = Param::new("max_step", 3);
+const TOLERANCE_PART1: Param<usize> = Param::for_part(1, "tolerance", 0);
+const TOLERANCE_PART2: Param<usize> = Param::for_part(2, "tolerance", 1);
+
+pub(crate) const PARAMS: &[&dyn Parameter] =
+    &[&MIN_STEP, &MAX_STEP, &TOLERANCE_PART1, &TOLERANCE_PART2];
+
+fn rules(tolerance: &Param<usize>) -> Rules {
+    Rules {
+        min_step: MIN_STEP.get(),
+        max_step: MAX_STEP.get(),
+        tolerance: tolerance.get(),
     }
-    (0..report.len()).any(|i| {
-        // Skip the i-th level
-        let tolerated_report = report.iter().take(i).chain(report.iter().skip(i + 1));
-        // Check if this is now safe
-        is_safe(tolerated_report)
-    })
+}
+
+fn count_safe(input: &Input, rules: &Rules) -> usize {
+    input.iter().filter(|report| is_safe(report, rules)).count()
+}
+
+#[aoc(day2, part1)]
+fn part1(input: &Input) -> usize {
+    count_safe(input, &rules(&TOLERANCE_PART1))
 }
 
 #[aoc(day2, part2)]
 fn part2(input: &Input) -> usize {
-    input.iter().filter(|report| is_safe_part2(report)).count()
+    count_safe(input, &rules(&TOLERANCE_PART2))
+}
+
+#[allow(unused)]
+fn print_diagnostics(input: &Input, rules: &Rules) {
+    let reports = input
+        .iter()
+        .map(|report| report.iter().join(" "))
+        .collect::<Vec<_>>();
+    let width = reports.iter().map(|report| report.len()).max().unwrap_or(0);
+    println!("{:width$}  {:24}  dampened", "report", "verdict");
+    for (report, levels) in reports.iter().zip(input) {
+        let dampened = match dampen(levels, rules) {
+            Some(removed) if removed.is_empty() => "safe".to_string(),
+            Some(removed) if removed.len() == 1 => format!("safe without level {}", removed[0]),
+            Some(removed) => format!("safe without levels {}", removed.iter().join(", ")),
+            None => "unsafe".to_string(),
+        };
+        let verdict = diagnose(levels, rules).to_string();
+        println!("{report:width$}  {verdict:24}  {dampened}");
+    }
 }
 
 #[cfg(test)]
@@ -66,4 +192,88 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 4);
     }
+
+    fn default_rules(tolerance: usize) -> Rules {
+        Rules {
+            min_step: 1,
+            max_step: 3,
+            tolerance,
+        }
+    }
+
+    #[test]
+    fn diagnose_example() {
+        let input = parse(EXAMPLE);
+        let rules = default_rules(1);
+        let verdicts = input
+            .iter()
+            .map(|report| diagnose(report, &rules))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            verdicts,
+            vec![
+                Verdict::Safe,
+                Verdict::StepTooLarge(2),
+                Verdict::StepTooLarge(3),
+                Verdict::MixedDirection(2),
+                Verdict::StepZero(3),
+                Verdict::Safe,
+            ]
+        );
+        let removed = input
+            .iter()
+            .map(|report| dampen(report, &rules))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            removed,
+            vec![
+                Some(vec![]),
+                None,
+                None,
+                Some(vec![2]),
+                Some(vec![3]),
+                Some(vec![])
+            ]
+        );
+        print_diagnostics(&input, &rules);
+    }
+
+    /// Check against removing every combination of up to `k` levels.
+    fn is_safe_brute_force(report: &[i32], rules: &Rules) -> bool {
+        (0..=rules.tolerance.min(report.len())).any(|k| {
+            (0..report.len()).combinations(k).any(|removed| {
+                let report = (0..report.len())
+                    .filter(|i| !removed.contains(i))
+                    .map(|i| report[i])
+                    .collect::<Vec<_>>();
+                diagnose(&report, rules) == Verdict::Safe
+            })
+        })
+    }
+
+    #[test]
+    fn dampen_brute_force() {
+        let reports = [
+            vec![1, 5, 2, 3, 9, 4],
+            vec![5, 1, 2, 3, 4],
+            vec![1, 2, 3, 4, 0],
+            vec![9, 1, 2, 8, 3, 4, 7],
+            vec![3, 3, 3, 3],
+            vec![1, 2, 10, 11, 3, 4],
+            vec![7],
+        ];
+        for report in &reports {
+            for tolerance in 0..=3 {
+                let rules = default_rules(tolerance);
+                assert_eq!(
+                    is_safe(report, &rules),
+                    is_safe_brute_force(report, &rules),
+                    "{report:?} with tolerance {tolerance}"
+                );
+                if let Some(removed) = dampen(report, &rules) {
+                    assert!(removed.len() <= tolerance);
+                }
+            }
+        }
+    }
 }
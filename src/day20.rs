@@ -1,3 +1,4 @@
+use crate::params::{Param, Parameter};
 use crate::util::{Direction, Vector2D};
 use aoc_runner_derive::{aoc, aoc_generator};
 use pathfinding::prelude::*;
@@ -102,14 +103,21 @@ fn find_cheats(maze: &Maze, cheat_duration: i32, min_saving: usize) -> usize {
     cheats.len()
 }
 
+const MIN_SAVING: Param<usize> = Param::new("min_saving", 100);
+const CHEAT_DURATION_PART1: Param<i32> = Param::for_part(1, "cheat_duration", 2);
+const CHEAT_DURATION_PART2: Param<i32> = Param::for_part(2, "cheat_duration", 20);
+
+pub(crate) const PARAMS: &[&dyn Parameter] =
+    &[&MIN_SAVING, &CHEAT_DURATION_PART1, &CHEAT_DURATION_PART2];
+
 #[aoc(day20, part1)]
 fn part1(maze: &Maze) -> usize {
-    find_cheats(maze, 2, 100)
+    find_cheats(maze, CHEAT_DURATION_PART1.get(), MIN_SAVING.get())
 }
 
 #[aoc(day20, part2)]
 fn part2(maze: &Maze) -> usize {
-    find_cheats(maze, 20, 100)
+    find_cheats(maze, CHEAT_DURATION_PART2.get(), MIN_SAVING.get())
 }
 
 #[cfg(test)]
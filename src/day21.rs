@@ -1,3 +1,4 @@
+use crate::params::{Param, Parameter};
 use crate::util::{Direction, Vector2D};
 use aoc_runner_derive::{aoc, aoc_generator};
 use bimap::BiHashMap;
@@ -164,14 +165,19 @@ fn solve(codes: &[String], num_robots: usize) -> usize {
         .sum()
 }
 
+const ROBOTS_PART1: Param<usize> = Param::for_part(1, "robots", 2);
+const ROBOTS_PART2: Param<usize> = Param::for_part(2, "robots", 25);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&ROBOTS_PART1, &ROBOTS_PART2];
+
 #[aoc(day21, part1)]
 fn part1(codes: &[String]) -> usize {
-    solve(codes, 2)
+    solve(codes, ROBOTS_PART1.get())
 }
 
 #[aoc(day21, part2)]
 fn part2(codes: &[String]) -> usize {
-    solve(codes, 25)
+    solve(codes, ROBOTS_PART2.get())
 }
 
 #[cfg(test)]
@@ -1,3 +1,4 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use std::collections::{HashMap, HashSet};
@@ -26,11 +27,16 @@ fn secrets(mut secret: u64) -> impl Iterator<Item = u64> {
     })
 }
 
+const ITERATIONS: Param<usize> = Param::new("iterations", 2000);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&ITERATIONS];
+
 #[aoc(day22, part1)]
 fn part1(input: &[u64]) -> u64 {
+    let iterations = ITERATIONS.get();
     input
         .iter()
-        .map(|&secret| secrets(secret).nth(2000).unwrap())
+        .map(|&secret| secrets(secret).nth(iterations).unwrap())
         .sum()
 }
 
@@ -57,9 +63,14 @@ fn price_changes(secret: u64) -> impl Iterator<Item = (u64, Change)> {
 #[aoc(day22, part2)]
 fn part2(input: &[u64]) -> u64 {
     // Pre-compute the price for every possible change for every monkey
+    let iterations = ITERATIONS.get();
     let all_prices_by_change = input
         .iter()
-        .map(|&secret| price_changes(secret).take(2000 + 1).collect::<Vec<_>>())
+        .map(|&secret| {
+            price_changes(secret)
+                .take(iterations + 1)
+                .collect::<Vec<_>>()
+        })
         .map(|changes| {
             let mut price_by_change = HashMap::<Change, u64>::new();
             for (price, change) in changes {
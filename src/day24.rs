@@ -427,7 +427,6 @@ mod tests {
 
     const EXAMPLE1: &str = include_str!("../examples/2024/day24-1.txt");
     const EXAMPLE2: &str = include_str!("../examples/2024/day24-2.txt");
-    const INPUT: &str = include_str!("../input/2024/day24.txt");
 
     #[test]
     fn part1_example1() {
@@ -441,7 +440,10 @@ mod tests {
 
     #[test]
     fn part2_mermaid() {
-        parse(INPUT).print_mermaid();
+        let Some(input) = crate::input::load_or_skip(24) else {
+            return;
+        };
+        parse(&input).print_mermaid();
     }
 
     #[test]
@@ -1,62 +1,419 @@
-use aoc_runner_derive::{aoc, aoc_generator};
-use regex::Regex;
-
-#[derive(Debug, Copy, Clone)]
-enum Instruction {
-    Mul(i32, i32),
-    Do,
-    Dont,
-}
-
-#[aoc_generator(day3)]
-fn parse(input: &str) -> Vec<Instruction> {
-    let re = Regex::new(r"(mul|do|don't)\((?:(\d+),(\d+))?\)").unwrap();
-    re.captures_iter(input)
-        .flat_map(|c| {
-            Some(match c.get(1).unwrap().as_str() {
-                "mul" => Instruction::Mul(
-                    c.get(2)?.as_str().parse().ok()?,
-                    c.get(3)?.as_str().parse().ok()?,
-                ),
-                "do" => Instruction::Do,
-                "don't" => Instruction::Dont,
-                _ => return None,
-            })
+use crate::params::{Param, Parameter};
+use aoc_runner_derive::aoc;
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+use std::io;
+use std::io::Read;
+use std::str::FromStr;
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operand {
+    /// A number with 1 to 3 digits.
+    Number(i64),
+    /// A register, named by a single lowercase letter.
+    Register(u8),
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Number(value) => write!(f, "{value}"),
+            Operand::Register(name) => write!(f, "{}", *name as char),
+        }
+    }
+}
+
+/// What an instruction accepts as an operand.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum OperandKind {
+    /// A number, or a register (if the language has registers).
+    Value,
+    /// Only a register, e.g. the target of an assignment.
+    Register,
+}
+
+struct Definition {
+    name: &'static str,
+    operands: &'static [OperandKind],
+    /// Whether the instruction is skipped while disabled by `don't()`.
+    conditional: bool,
+    execute: fn(&mut Machine, &[Operand]),
+}
+
+const MUL: Definition = Definition {
+    name: "mul",
+    operands: &[OperandKind::Value, OperandKind::Value],
+    conditional: true,
+    execute: |machine, args| machine.total += machine.value(args[0]) * machine.value(args[1]),
+};
+
+const ADD: Definition = Definition {
+    name: "add",
+    operands: &[OperandKind::Value, OperandKind::Value],
+    conditional: true,
+    execute: |machine, args| machine.total += machine.value(args[0]) + machine.value(args[1]),
+};
+
+const SUB: Definition = Definition {
+    name: "sub",
+    operands: &[OperandKind::Value, OperandKind::Value],
+    conditional: true,
+    execute: |machine, args| machine.total += machine.value(args[0]) - machine.value(args[1]),
+};
+
+const SET: Definition = Definition {
+    name: "set",
+    operands: &[OperandKind::Register, OperandKind::Value],
+    conditional: true,
+    execute: |machine, args| {
+        let Operand::Register(name) = args[0] else {
+            unreachable!("target must be a register");
+        };
+        machine.registers[(name - b'a') as usize] = machine.value(args[1]);
+    },
+};
+
+const DO: Definition = Definition {
+    name: "do",
+    operands: &[],
+    conditional: false,
+    execute: |machine, _| {
+        if machine.nested_scopes {
+            machine.disabled = machine.disabled.saturating_sub(1);
+        } else {
+            machine.disabled = 0;
+        }
+    },
+};
+
+const DONT: Definition = Definition {
+    name: "don't",
+    operands: &[],
+    conditional: false,
+    execute: |machine, _| {
+        if machine.nested_scopes {
+            machine.disabled += 1;
+        } else {
+            machine.disabled = 1;
+        }
+    },
+};
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Dialect {
+    /// Only `mul`.
+    Basic,
+    /// `mul`, `do` and `don't`.
+    Conditional,
+    /// All instructions, with registers and nested `do`/`don't` scopes.
+    Extended,
+}
+
+impl FromStr for Dialect {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "basic" => Dialect::Basic,
+            "conditional" => Dialect::Conditional,
+            "extended" => Dialect::Extended,
+            _ => return Err(format!("unknown dialect: {s}")),
         })
-        .collect::<Vec<_>>()
+    }
 }
 
-#[aoc(day3, part1)]
-fn part1(input: &[Instruction]) -> i32 {
-    let mut sum = 0;
-    for instruction in input {
-        if let Instruction::Mul(left, right) = instruction {
-            sum += left * right
+impl Display for Dialect {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Dialect::Basic => "basic",
+            Dialect::Conditional => "conditional",
+            Dialect::Extended => "extended",
+        })
+    }
+}
+
+struct Language {
+    definitions: Vec<&'static Definition>,
+    /// Whether operands can refer to registers.
+    registers: bool,
+    /// Whether `do()` only undoes the last `don't()`, rather than all of them.
+    nested_scopes: bool,
+}
+
+impl From<Dialect> for Language {
+    fn from(dialect: Dialect) -> Self {
+        match dialect {
+            Dialect::Basic => Language {
+                definitions: vec![&MUL],
+                registers: false,
+                nested_scopes: false,
+            },
+            Dialect::Conditional => Language {
+                definitions: vec![&MUL, &DO, &DONT],
+                registers: false,
+                nested_scopes: false,
+            },
+            Dialect::Extended => Language {
+                definitions: vec![&MUL, &ADD, &SUB, &SET, &DO, &DONT],
+                registers: true,
+                nested_scopes: true,
+            },
+        }
+    }
+}
+
+struct Instruction {
+    definition: &'static Definition,
+    args: Vec<Operand>,
+    /// Byte offset in the corrupted memory.
+    offset: usize,
+}
+
+impl Display for Instruction {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}({})",
+            self.definition.name,
+            self.args.iter().join(",")
+        )
+    }
+}
+
+impl Language {
+    /// Try to match an instruction at the start of `input`.
+    /// Returns the instruction and its length in bytes.
+    fn match_at(&self, input: &[u8], offset: usize) -> Option<(Instruction, usize)> {
+        self.definitions
+            .iter()
+            .find_map(|&definition| self.match_definition(definition, input, offset))
+    }
+
+    fn match_definition(
+        &self,
+        definition: &'static Definition,
+        input: &[u8],
+        offset: usize,
+    ) -> Option<(Instruction, usize)> {
+        let mut pos = definition.name.len();
+        if !input.starts_with(definition.name.as_bytes()) || input.get(pos) != Some(&b'(') {
+            return None;
+        }
+        pos += 1;
+        let mut args = Vec::with_capacity(definition.operands.len());
+        for (i, &kind) in definition.operands.iter().enumerate() {
+            if i > 0 {
+                if input.get(pos) != Some(&b',') {
+                    return None;
+                }
+                pos += 1;
+            }
+            let (operand, len) = self.match_operand(&input[pos..])?;
+            if kind == OperandKind::Register && !matches!(operand, Operand::Register(_)) {
+                return None;
+            }
+            args.push(operand);
+            pos += len;
+        }
+        if input.get(pos) != Some(&b')') {
+            return None;
+        }
+        pos += 1;
+        let instruction = Instruction {
+            definition,
+            args,
+            offset,
         };
+        Some((instruction, pos))
+    }
+
+    fn match_operand(&self, input: &[u8]) -> Option<(Operand, usize)> {
+        let digits = input.iter().take_while(|c| c.is_ascii_digit()).count();
+        match (digits, input.first()) {
+            (1..=3, _) => {
+                let value = std::str::from_utf8(&input[..digits])
+                    .unwrap()
+                    .parse()
+                    .ok()?;
+                Some((Operand::Number(value), digits))
+            }
+            (0, Some(&c)) if self.registers && c.is_ascii_lowercase() => {
+                Some((Operand::Register(c), 1))
+            }
+            _ => None,
+        }
+    }
+
+    /// The length of the longest possible instruction.
+    fn max_instruction_len(&self) -> usize {
+        self.definitions
+            .iter()
+            .map(|definition| {
+                // Name, parentheses, operands of up to 3 bytes, and commas in between
+                let arity = definition.operands.len();
+                definition.name.len() + 2 + 3 * arity + arity.saturating_sub(1)
+            })
+            .max()
+            .unwrap_or(0)
     }
-    sum
 }
 
-#[aoc(day3, part2)]
-fn part2(input: &[Instruction]) -> i32 {
-    let mut sum = 0;
-    let mut enabled = true;
-    for instruction in input {
-        match instruction {
-            Instruction::Mul(left, right) => {
-                if enabled {
-                    sum += left * right
+const CHUNK_SIZE: usize = 64 * 1024;
+
+/// Scans corrupted memory for instructions, reading it chunk by chunk.
+///
+/// Only the current chunk and the start of a possible instruction are kept in memory,
+/// so instructions that are split across two chunks are still found.
+struct Scanner<'a, R> {
+    language: &'a Language,
+    reader: R,
+    buffer: Vec<u8>,
+    /// Position of the next byte to scan in the buffer.
+    pos: usize,
+    /// Offset of the start of the buffer in the memory.
+    offset: usize,
+    chunk_size: usize,
+    eof: bool,
+}
+
+impl<'a, R: Read> Scanner<'a, R> {
+    fn new(language: &'a Language, reader: R) -> Self {
+        Self::with_chunk_size(language, reader, CHUNK_SIZE)
+    }
+
+    fn with_chunk_size(language: &'a Language, reader: R, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0);
+        Self {
+            language,
+            reader,
+            buffer: Vec::new(),
+            pos: 0,
+            offset: 0,
+            chunk_size,
+            eof: false,
+        }
+    }
+
+    /// Make sure there are at least `len` bytes left to scan in the buffer,
+    /// unless the end of the memory is reached.
+    fn fill(&mut self, len: usize) -> io::Result<()> {
+        while !self.eof && self.buffer.len() - self.pos < len {
+            // Discard the bytes that were already scanned
+            self.buffer.drain(..self.pos);
+            self.offset += self.pos;
+            self.pos = 0;
+            // Read the next chunk
+            let start = self.buffer.len();
+            self.buffer.resize(start + self.chunk_size, 0);
+            let read = loop {
+                match self.reader.read(&mut self.buffer[start..]) {
+                    Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+                    result => break result,
                 }
+            };
+            let read = read.inspect_err(|_| self.buffer.truncate(start))?;
+            self.buffer.truncate(start + read);
+            self.eof = read == 0;
+        }
+        Ok(())
+    }
+}
+
+impl<R: Read> Iterator for Scanner<'_, R> {
+    type Item = io::Result<Instruction>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let max_len = self.language.max_instruction_len();
+        loop {
+            if let Err(e) = self.fill(max_len) {
+                return Some(Err(e));
             }
-            Instruction::Do => {
-                enabled = true;
+            let input = &self.buffer[self.pos..];
+            if input.is_empty() {
+                return None;
             }
-            Instruction::Dont => {
-                enabled = false;
+            if let Some((instruction, len)) = self.language.match_at(input, self.offset + self.pos)
+            {
+                self.pos += len;
+                return Some(Ok(instruction));
             }
-        };
+            self.pos += 1;
+        }
     }
-    sum
+}
+
+#[derive(Debug, Default)]
+struct Machine {
+    total: i64,
+    registers: [i64; 26],
+    /// Number of `don't()` scopes we're in.
+    disabled: usize,
+    nested_scopes: bool,
+    trace: bool,
+}
+
+impl Machine {
+    fn new(language: &Language, trace: bool) -> Self {
+        Self {
+            nested_scopes: language.nested_scopes,
+            trace,
+            ..Default::default()
+        }
+    }
+
+    fn value(&self, operand: Operand) -> i64 {
+        match operand {
+            Operand::Number(value) => value,
+            Operand::Register(name) => self.registers[(name - b'a') as usize],
+        }
+    }
+
+    fn execute(&mut self, instruction: &Instruction) {
+        let skipped = instruction.definition.conditional && self.disabled > 0;
+        if self.trace {
+            let status = if skipped { " (disabled)" } else { "" };
+            println!("{:>8}: {instruction}{status}", instruction.offset);
+        }
+        if !skipped {
+            (instruction.definition.execute)(self, &instruction.args);
+        }
+    }
+}
+
+fn run(memory: impl Read, dialect: Dialect, trace: bool) -> io::Result<i64> {
+    let language = Language::from(dialect);
+    let mut machine = Machine::new(&language, trace);
+    for instruction in Scanner::new(&language, memory) {
+        machine.execute(&instruction?);
+    }
+    Ok(machine.total)
+}
+
+const DIALECT_PART1: Param<Dialect> = Param::for_part(1, "dialect", Dialect::Basic);
+const DIALECT_PART2: Param<Dialect> = Param::for_part(2, "dialect", Dialect::Conditional);
+const TRACE: Param<bool> = Param::new("trace", false);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&DIALECT_PART1, &DIALECT_PART2, &TRACE];
+
+/// Run part 1 on memory read from any source (such as a file),
+/// without loading all of it into memory first.
+pub(crate) fn stream_part1(memory: impl Read) -> io::Result<i64> {
+    run(memory, DIALECT_PART1.get(), TRACE.get())
+}
+
+/// Run part 2 on memory read from any source, like [`stream_part1`].
+pub(crate) fn stream_part2(memory: impl Read) -> io::Result<i64> {
+    run(memory, DIALECT_PART2.get(), TRACE.get())
+}
+
+#[aoc(day3, part1)]
+fn part1(input: &str) -> i64 {
+    stream_part1(input.as_bytes()).unwrap()
+}
+
+#[aoc(day3, part2)]
+fn part2(input: &str) -> i64 {
+    stream_part2(input.as_bytes()).unwrap()
 }
 
 #[cfg(test)]
@@ -66,12 +423,97 @@ mod tests {
     #[test]
     fn part1_example() {
         let example = r"xmul(2,4)%&mul[3,7]!@^do_not_mul(5,5)+mul(32,64]then(mul(11,8)mul(8,5))";
-        assert_eq!(part1(&parse(example)), 161);
+        assert_eq!(part1(example), 161);
     }
 
     #[test]
     fn part2_example() {
         let example = r"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
-        assert_eq!(part2(&parse(example)), 48);
+        assert_eq!(part2(example), 48);
+    }
+
+    #[test]
+    fn tokenize_example() {
+        let example = r"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let language = Language::from(Dialect::Conditional);
+        let instructions = Scanner::new(&language, example.as_bytes())
+            .map(|instruction| instruction.unwrap())
+            .map(|instruction| (instruction.offset, instruction.to_string()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            instructions,
+            vec![
+                (1, "mul(2,4)".to_string()),
+                (20, "don't()".to_string()),
+                (28, "mul(5,5)".to_string()),
+                (48, "mul(11,8)".to_string()),
+                (59, "do()".to_string()),
+                (64, "mul(8,5)".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn strict_operands() {
+        let memory = "mul(1234,5)mul(123,5)mul(,5)mul(a,5)";
+        assert_eq!(run(memory.as_bytes(), Dialect::Basic, false).unwrap(), 615);
+        // Only registers can be assigned to
+        let memory = "set(1,5)set(b,5)mul(b,2)";
+        let language = Language::from(Dialect::Extended);
+        let instructions = Scanner::new(&language, memory.as_bytes())
+            .map(|instruction| instruction.unwrap().to_string())
+            .collect::<Vec<_>>();
+        assert_eq!(instructions, ["set(b,5)", "mul(b,2)"]);
+        assert_eq!(
+            run(memory.as_bytes(), Dialect::Extended, false).unwrap(),
+            10
+        );
+    }
+
+    #[test]
+    fn extended_example() {
+        let example = "set(a,12)mul(a,2)don't()add(1,2)don't()do()sub(9,a)do()sub(a,2)add(b,1)";
+        let total = run(example.as_bytes(), Dialect::Extended, true).unwrap();
+        assert_eq!(total, 24 + 10 + 1);
+    }
+
+    #[test]
+    fn scan_chunks() {
+        let example = r"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let language = Language::from(Dialect::Conditional);
+        let scan = |chunk_size| {
+            Scanner::with_chunk_size(&language, example.as_bytes(), chunk_size)
+                .map(|instruction| instruction.unwrap())
+                .map(|instruction| (instruction.offset, instruction.to_string()))
+                .collect::<Vec<_>>()
+        };
+        let expected = scan(CHUNK_SIZE);
+        for chunk_size in 1..=example.len() {
+            assert_eq!(scan(chunk_size), expected, "chunk size {chunk_size}");
+        }
+    }
+
+    #[test]
+    fn scan_constant_memory() {
+        // Lots of garbage before the only instruction, which must not all be kept in memory
+        let memory = io::repeat(b'x').take(1_000_000).chain(&b"mul(12,34)"[..]);
+        let language = Language::from(Dialect::Basic);
+        let mut scanner = Scanner::with_chunk_size(&language, memory, 16);
+        let instruction = scanner.next().unwrap().unwrap();
+        assert_eq!(instruction.offset, 1_000_000);
+        assert!(scanner.buffer.capacity() < 64);
+        assert!(scanner.next().is_none());
+    }
+
+    #[test]
+    fn stream_file() {
+        let example = r"xmul(2,4)&mul[3,7]!^don't()_mul(5,5)+mul(32,64](mul(11,8)undo()?mul(8,5))";
+        let path = std::env::temp_dir().join("aoc-2024-day3-stream.txt");
+        std::fs::write(&path, example.repeat(10_000)).unwrap();
+        let file = std::io::BufReader::new(std::fs::File::open(&path).unwrap());
+        assert_eq!(stream_part1(file).unwrap(), 161 * 10_000);
+        let file = std::fs::File::open(&path).unwrap();
+        assert_eq!(stream_part2(file).unwrap(), 48 * 10_000);
+        std::fs::remove_file(&path).unwrap();
     }
 }
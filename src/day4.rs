@@ -1,116 +1,218 @@
+use crate::util::Vector2D;
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 
 struct Crossword {
-    size: usize,
-    lines: Vec<String>,
+    width: i32,
+    height: i32,
+    lines: Vec<Vec<u8>>,
 }
 
 #[aoc_generator(day4)]
 fn parse(input: &str) -> Crossword {
-    let lines: Vec<String> = input.lines().map(|line| line.to_string()).collect();
-    let size = lines.len();
-    assert_eq!(lines[0].len(), size);
-    Crossword { size, lines }
+    let lines: Vec<Vec<u8>> = input.lines().map(|line| line.as_bytes().to_vec()).collect();
+    let height = lines.len() as i32;
+    let width = lines[0].len() as i32;
+    assert!(
+        lines.iter().all(|line| line.len() == width as usize),
+        "crossword must be rectangular"
+    );
+    Crossword {
+        width,
+        height,
+        lines,
+    }
 }
 
-impl Crossword {
-    fn get(&self, x: usize, y: usize) -> u8 {
-        self.lines[y].as_bytes()[x]
-    }
+/// A word found in the crossword, starting at `start` and going in `direction`.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Match<'a> {
+    word: &'a str,
+    start: Vector2D,
+    direction: Vector2D,
+}
 
-    fn horizontals(&self) -> Vec<Vec<u8>> {
-        (0..self.size)
-            .map(|y| (0..self.size).map(|x| self.get(x, y)).collect())
-            .collect()
+/// One direction out of every pair of opposite directions.
+const HALF_DIRECTIONS: [Vector2D; 4] = [
+    Vector2D::new(1, 0),
+    Vector2D::new(1, 1),
+    Vector2D::new(0, 1),
+    Vector2D::new(-1, 1),
+];
+
+impl Crossword {
+    fn get(&self, pos: Vector2D) -> Option<u8> {
+        if (0..self.width).contains(&pos.x()) && (0..self.height).contains(&pos.y()) {
+            Some(self.lines[pos.y() as usize][pos.x() as usize])
+        } else {
+            None
+        }
     }
 
-    fn verticals(&self) -> Vec<Vec<u8>> {
-        (0..self.size)
-            .map(|x| (0..self.size).map(|y| self.get(x, y)).collect())
-            .collect()
+    fn positions(&self) -> impl Iterator<Item = Vector2D> + use<'_> {
+        (0..self.height)
+            .cartesian_product(0..self.width)
+            .map(|(y, x)| Vector2D::new(x, y))
     }
 
-    fn main_diagonals(&self) -> Vec<Vec<u8>> {
-        let mut result = Vec::new();
-        for offset in 0..self.size {
-            result.push(self.get_diagonal(offset, 0, 1, 1));
-            if offset != 0 {
-                result.push(self.get_diagonal(0, offset, 1, 1));
+    fn has_word_at(&self, word: &str, start: Vector2D, direction: Vector2D) -> bool {
+        let mut pos = start;
+        for c in word.bytes() {
+            if self.get(pos) != Some(c) {
+                return false;
             }
+            pos += direction;
         }
-        result
+        true
     }
 
-    fn anti_diagonals(&self) -> Vec<Vec<u8>> {
+    /// Find all occurrences of the given words, in all 8 directions.
+    /// Empty words are skipped, since they don't occupy any cell.
+    ///
+    /// A palindrome reads the same in both directions, so each occurrence
+    /// is only reported once (rather than once for each direction).
+    fn search<'a>(&self, words: &[&'a str]) -> Vec<Match<'a>> {
+        let all_directions = Vector2D::zero().neighbours_diagonal().collect::<Vec<_>>();
         let mut result = Vec::new();
-        for offset in 0..self.size {
-            result.push(self.get_diagonal(offset, 0, -1, 1));
-            if offset != 0 {
-                result.push(self.get_diagonal(self.size - 1, offset, -1, 1));
+        for &word in words.iter().unique() {
+            if word.is_empty() {
+                continue;
+            }
+            let directions = if word.len() == 1 {
+                // Every direction reads the same
+                &HALF_DIRECTIONS[..1]
+            } else if word.bytes().eq(word.bytes().rev()) {
+                // Opposite directions read the same
+                &HALF_DIRECTIONS[..]
+            } else {
+                &all_directions[..]
+            };
+            for start in self.positions() {
+                for &direction in directions {
+                    if self.has_word_at(word, start, direction) {
+                        result.push(Match {
+                            word,
+                            start,
+                            direction,
+                        });
+                    }
+                }
             }
         }
         result
     }
+}
 
-    fn get_diagonal(&self, mut x: usize, mut y: usize, step_x: isize, step_y: isize) -> Vec<u8> {
-        let mut result = Vec::new();
-        while x < self.size && y < self.size {
-            result.push(self.get(x, y));
-            x = match x.checked_add_signed(step_x) {
-                Some(x) => x,
-                None => break,
-            };
-            y = match y.checked_add_signed(step_y) {
-                Some(y) => y,
-                None => break,
-            };
+#[aoc(day4, part1)]
+fn part1(crossword: &Crossword) -> usize {
+    crossword.search(&["XMAS"]).len()
+}
+
+/// A small grid of letters to look for, where `.` matches any letter.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct Pattern {
+    rows: Vec<Vec<Option<u8>>>,
+}
+
+impl Pattern {
+    /// Parse a pattern, with rows separated by newlines or slashes (e.g. `M.S / .A. / M.S`).
+    fn parse(input: &str) -> Self {
+        let rows = input
+            .split(['\n', '/'])
+            .map(|row| {
+                row.trim()
+                    .bytes()
+                    .map(|c| if c == b'.' { None } else { Some(c) })
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>();
+        assert!(
+            rows.iter().all(|row| row.len() == rows[0].len()),
+            "pattern must be rectangular"
+        );
+        Pattern { rows }
+    }
+
+    fn width(&self) -> usize {
+        self.rows[0].len()
+    }
+
+    fn height(&self) -> usize {
+        self.rows.len()
+    }
+
+    /// Rotate clockwise by 90 degrees.
+    fn rotate(&self) -> Self {
+        let rows = (0..self.width())
+            .map(|x| (0..self.height()).rev().map(|y| self.rows[y][x]).collect())
+            .collect();
+        Pattern { rows }
+    }
+
+    /// Mirror left to right.
+    fn reflect(&self) -> Self {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().rev().copied().collect())
+            .collect();
+        Pattern { rows }
+    }
+
+    /// All distinct rotations and reflections of this pattern.
+    fn variants(&self) -> Vec<Pattern> {
+        let mut result = Vec::<Pattern>::new();
+        for mut variant in [self.clone(), self.reflect()] {
+            for _ in 0..4 {
+                if !result.contains(&variant) {
+                    result.push(variant.clone());
+                }
+                variant = variant.rotate();
+            }
         }
         result
     }
 }
 
-fn count_xmas(scan_line: &[u8]) -> usize {
-    scan_line
-        .windows(4)
-        .filter(|&window| matches!(window, b"XMAS" | b"SAMX"))
-        .count()
-}
-
-#[aoc(day4, part1)]
-fn part1(crossword: &Crossword) -> usize {
-    crossword
-        .horizontals()
-        .iter()
-        .chain(crossword.verticals().iter())
-        .chain(crossword.main_diagonals().iter())
-        .chain(crossword.anti_diagonals().iter())
-        .map(|scan_line| count_xmas(scan_line))
-        .sum()
+/// A pattern found in the crossword, with its top left corner at `start`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct PatternMatch {
+    start: Vector2D,
+    variant: Pattern,
 }
 
 impl Crossword {
-    fn is_x_mas_at(&self, x: usize, y: usize) -> bool {
-        x + 2 < self.size
-            && y + 2 < self.size
-            && self.get(x + 1, y + 1) == b'A'
-            && matches!(
-                (self.get(x, y), self.get(x + 2, y + 2)),
-                (b'M', b'S') | (b'S', b'M')
-            )
-            && matches!(
-                (self.get(x + 2, y), self.get(x, y + 2)),
-                (b'M', b'S') | (b'S', b'M')
-            )
+    fn has_pattern_at(&self, pattern: &Pattern, start: Vector2D) -> bool {
+        pattern.rows.iter().enumerate().all(|(y, row)| {
+            row.iter().enumerate().all(|(x, &c)| {
+                let letter = self.get(start + Vector2D::new(x as i32, y as i32));
+                letter.is_some() && (c.is_none() || c == letter)
+            })
+        })
+    }
+
+    /// Find all occurrences of the given pattern, in any rotation or reflection.
+    fn search_pattern(&self, pattern: &Pattern) -> Vec<PatternMatch> {
+        let variants = pattern.variants();
+        let mut result = Vec::new();
+        for start in self.positions() {
+            for variant in &variants {
+                if self.has_pattern_at(variant, start) {
+                    result.push(PatternMatch {
+                        start,
+                        variant: variant.clone(),
+                    });
+                }
+            }
+        }
+        result
     }
 }
 
 #[aoc(day4, part2)]
 fn part2(crossword: &Crossword) -> usize {
-    (0..crossword.size)
-        .cartesian_product(0..crossword.size)
-        .filter(move |&(x, y)| crossword.is_x_mas_at(x, y))
-        .count()
+    let x_mas = Pattern::parse("M.S / .A. / M.S");
+    crossword.search_pattern(&x_mas).len()
 }
 
 #[cfg(test)]
@@ -137,4 +239,82 @@ MXMXAXMASX";
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 9);
     }
+
+    #[test]
+    fn search_example() {
+        let crossword = parse("..X...\n.SAMX.\n.A..A.\nXMAS.S\n.X....");
+        let matches = crossword.search(&["XMAS"]);
+        assert_eq!(
+            matches,
+            vec![
+                Match {
+                    word: "XMAS",
+                    start: Vector2D::new(2, 0),
+                    direction: Vector2D::new(1, 1)
+                },
+                Match {
+                    word: "XMAS",
+                    start: Vector2D::new(4, 1),
+                    direction: Vector2D::new(-1, 0)
+                },
+                Match {
+                    word: "XMAS",
+                    start: Vector2D::new(0, 3),
+                    direction: Vector2D::new(1, 0)
+                },
+                Match {
+                    word: "XMAS",
+                    start: Vector2D::new(1, 4),
+                    direction: Vector2D::new(0, -1)
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn pattern_variants() {
+        let x_mas = Pattern::parse("M.S / .A. / M.S");
+        assert_eq!(
+            x_mas.variants(),
+            vec![
+                Pattern::parse("M.S/.A./M.S"),
+                Pattern::parse("M.M/.A./S.S"),
+                Pattern::parse("S.M/.A./S.M"),
+                Pattern::parse("S.S/.A./M.M"),
+            ]
+        );
+        assert_eq!(Pattern::parse("XM").variants().len(), 4);
+        assert_eq!(Pattern::parse("XM\n.A").variants().len(), 8);
+        assert_eq!(Pattern::parse("A.\n.A").variants().len(), 2);
+    }
+
+    #[test]
+    fn search_pattern_example() {
+        let crossword = parse("XM..\n.A..\n..A.\n..MX");
+        let matches = crossword.search_pattern(&Pattern::parse("XM/.A"));
+        assert_eq!(
+            matches,
+            vec![
+                PatternMatch {
+                    start: Vector2D::new(0, 0),
+                    variant: Pattern::parse("XM/.A"),
+                },
+                PatternMatch {
+                    start: Vector2D::new(2, 2),
+                    variant: Pattern::parse("A./MX"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn search_palindromes() {
+        let crossword = parse("ABA\nBAB");
+        assert_eq!(crossword.search(&["ABA"]).len(), 1);
+        assert_eq!(crossword.search(&["BAB"]).len(), 1);
+        assert_eq!(crossword.search(&["AB", "BA"]).len(), 14);
+        assert_eq!(crossword.search(&["A", "A"]).len(), 3);
+        assert_eq!(crossword.search(&[""]).len(), 0);
+        assert_eq!(crossword.search(&["", "A"]).len(), 3);
+    }
 }
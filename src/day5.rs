@@ -1,12 +1,24 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
-use std::collections::VecDeque;
+use itertools::Itertools;
+use pathfinding::prelude::strongly_connected_components;
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::iter::once;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct Rule {
     before: u32,
     after: u32,
 }
 
+impl Display for Rule {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}|{}", self.before, self.after)
+    }
+}
+
 type Update = Vec<u32>;
 
 type Input = (Vec<Rule>, Vec<Update>);
@@ -31,57 +43,295 @@ fn parse(input: &str) -> Input {
     (rules, updates)
 }
 
-fn is_update_valid(update: &Update, rules: &[Rule]) -> bool {
-    for rule in rules {
-        if let Some(before_index) = update.iter().position(|&x| x == rule.before) {
-            if let Some(after_index) = update.iter().position(|&x| x == rule.after) {
-                if before_index > after_index {
-                    return false;
+/// The rules, indexed by the page that must come first.
+struct RuleIndex {
+    after: HashMap<u32, HashSet<u32>>,
+}
+
+impl RuleIndex {
+    fn new(rules: &[Rule]) -> Self {
+        let mut after = HashMap::<u32, HashSet<u32>>::new();
+        for rule in rules {
+            after.entry(rule.before).or_default().insert(rule.after);
+        }
+        Self { after }
+    }
+
+    fn must_precede(&self, before: u32, after: u32) -> bool {
+        self.after
+            .get(&before)
+            .is_some_and(|pages| pages.contains(&after))
+    }
+
+    /// Check whether `after` must come after `before` through a chain of at least two rules.
+    fn is_implied(&self, before: u32, after: u32) -> bool {
+        let mut visited = HashSet::new();
+        let mut queue = self
+            .after
+            .get(&before)
+            .into_iter()
+            .flatten()
+            .copied()
+            .filter(|&page| page != after)
+            .collect::<VecDeque<_>>();
+        while let Some(page) = queue.pop_front() {
+            if !visited.insert(page) {
+                continue;
+            }
+            for &next in self.after.get(&page).into_iter().flatten() {
+                if next == after {
+                    return true;
                 }
+                queue.push_back(next);
             }
         }
+        false
+    }
+
+    /// Groups of pages that must all come before each other through some chain of rules,
+    /// i.e. the strongly connected components of the rules that contain a cycle.
+    fn cycles(&self) -> Vec<Vec<u32>> {
+        let pages = self
+            .after
+            .iter()
+            .flat_map(|(&before, after)| once(before).chain(after.iter().copied()))
+            .unique()
+            .collect::<Vec<_>>();
+        let successors = |page: &u32| self.after.get(page).into_iter().flatten().copied();
+        strongly_connected_components(&pages, successors)
+            .into_iter()
+            .filter(|pages| pages.len() > 1 || self.must_precede(pages[0], pages[0]))
+            .map(|pages| pages.into_iter().sorted().collect::<Vec<_>>())
+            .sorted()
+            .collect()
+    }
+
+    fn is_update_valid(&self, update: &Update) -> bool {
+        update
+            .iter()
+            .tuple_combinations()
+            .all(|(&left, &right)| !self.must_precede(right, left))
+    }
+
+    /// Reorder the pages of an update so it satisfies all rules,
+    /// using a topological sort of the rules between those pages.
+    fn fix_update(&self, update: &Update) -> Result<Update, CycleError> {
+        let mut incoming = update
+            .iter()
+            .map(|&page| (page, 0usize))
+            .collect::<HashMap<_, _>>();
+        for &page in update {
+            for after in self.after.get(&page).into_iter().flatten() {
+                if let Some(count) = incoming.get_mut(after) {
+                    *count += 1;
+                }
+            }
+        }
+        let mut result = Vec::with_capacity(update.len());
+        // Keep pages in their original order where the rules allow it
+        let mut ready = update
+            .iter()
+            .copied()
+            .filter(|page| incoming[page] == 0)
+            .rev()
+            .collect::<Vec<_>>();
+        while let Some(page) = ready.pop() {
+            result.push(page);
+            let mut next = Vec::new();
+            for after in self.after.get(&page).into_iter().flatten() {
+                if let Some(count) = incoming.get_mut(after) {
+                    *count -= 1;
+                    if *count == 0 {
+                        next.push(*after);
+                    }
+                }
+            }
+            next.sort_by_key(|page| update.iter().position(|x| x == page));
+            ready.extend(next.into_iter().rev());
+        }
+        if result.len() < update.len() {
+            // The pages that could not be ordered are in a cycle, or must come after one
+            let remaining = update
+                .iter()
+                .copied()
+                .filter(|page| incoming[page] > 0)
+                .collect::<Vec<_>>();
+            let successors = |page: &u32| {
+                let after = self.after.get(page);
+                let after = after.into_iter().flatten().copied();
+                after
+                    .filter(|page| remaining.contains(page))
+                    .collect::<Vec<_>>()
+            };
+            let in_cycle = strongly_connected_components(&remaining, successors)
+                .into_iter()
+                .filter(|pages| pages.len() > 1 || self.must_precede(pages[0], pages[0]))
+                .flatten()
+                .collect::<HashSet<_>>();
+            let pages = remaining
+                .into_iter()
+                .filter(|page| in_cycle.contains(page))
+                .collect();
+            return Err(CycleError { pages });
+        }
+        Ok(result)
     }
-    true
 }
 
-#[aoc(day5, part1)]
-fn part1((rules, updates): &Input) -> u32 {
-    updates
+/// The rules between the pages of an update contain a cycle,
+/// so there is no valid order.
+#[derive(Debug, Clone, Eq, PartialEq)]
+struct CycleError {
+    /// The pages that form the cycle(s), in the order of the update.
+    pages: Vec<u32>,
+}
+
+impl Display for CycleError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "rules contain a cycle between pages {}",
+            self.pages.iter().join(", ")
+        )
+    }
+}
+
+impl Error for CycleError {}
+
+/// Problems found in the rules and updates.
+#[derive(Debug, Default)]
+struct Analysis {
+    /// Rules that already follow from a chain of other rules.
+    redundant: Vec<Rule>,
+    /// Pairs of pages that must each come before the other.
+    contradictions: Vec<(u32, u32)>,
+    /// Groups of pages whose rules form a cycle (including contradictions),
+    /// so they can't all appear in one update.
+    cycles: Vec<Vec<u32>>,
+    /// Pages in updates that don't appear in any rule.
+    unconstrained: Vec<u32>,
+    /// For each invalid update (by index), the rules it breaks.
+    violations: Vec<(usize, Vec<Rule>)>,
+}
+
+fn analyze((rules, updates): &Input) -> Analysis {
+    let index = RuleIndex::new(rules);
+    let redundant = rules
         .iter()
-        .filter(|update| is_update_valid(update, rules))
-        .map(|update| update[update.len() / 2])
-        .sum()
+        .copied()
+        .filter(|rule| index.is_implied(rule.before, rule.after))
+        .collect();
+    let contradictions = rules
+        .iter()
+        .filter(|rule| rule.before < rule.after && index.must_precede(rule.after, rule.before))
+        .map(|rule| (rule.before, rule.after))
+        .collect();
+    let cycles = index.cycles();
+    let in_rules = rules
+        .iter()
+        .flat_map(|rule| [rule.before, rule.after])
+        .collect::<HashSet<_>>();
+    let unconstrained = updates
+        .iter()
+        .flatten()
+        .copied()
+        .filter(|page| !in_rules.contains(page))
+        .unique()
+        .sorted()
+        .collect();
+    let violations = updates
+        .iter()
+        .enumerate()
+        .map(|(i, update)| {
+            let broken = rules
+                .iter()
+                .copied()
+                .filter(|rule| {
+                    let before = update.iter().position(|&x| x == rule.before);
+                    let after = update.iter().position(|&x| x == rule.after);
+                    matches!((before, after), (Some(before), Some(after)) if before > after)
+                })
+                .collect::<Vec<_>>();
+            (i, broken)
+        })
+        .filter(|(_, broken)| !broken.is_empty())
+        .collect();
+    Analysis {
+        redundant,
+        contradictions,
+        cycles,
+        unconstrained,
+        violations,
+    }
 }
 
-fn fix_update(update: &Update, rules: &[Rule]) -> Update {
-    debug_assert!(!is_update_valid(update, rules));
-    let mut queue = VecDeque::from(update.to_vec());
-    let mut update = Vec::new();
-    while let Some(page) = queue.pop_front() {
-        let mut index = update.len();
-        // Add new page to the end
-        update.insert(index, page);
-        while !is_update_valid(&update, rules) {
-            assert!(index > 0);
-            // Move new page to the front
-            update.swap(index - 1, index);
-            index -= 1;
+impl Display for Analysis {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "redundant rules: {}", self.redundant.len())?;
+        for rule in &self.redundant {
+            writeln!(f, "  {rule}")?;
         }
+        writeln!(f, "contradictions: {}", self.contradictions.len())?;
+        for (x, y) in &self.contradictions {
+            writeln!(f, "  {x}|{y} and {y}|{x}")?;
+        }
+        writeln!(f, "cycles: {}", self.cycles.len())?;
+        for pages in &self.cycles {
+            writeln!(f, "  between pages {}", pages.iter().join(", "))?;
+        }
+        writeln!(
+            f,
+            "pages without rules: {}",
+            self.unconstrained.iter().join(", ")
+        )?;
+        writeln!(f, "invalid updates: {}", self.violations.len())?;
+        for (i, broken) in &self.violations {
+            writeln!(f, "  update {i} breaks {}", broken.iter().join(", "))?;
+        }
+        Ok(())
     }
-    debug_assert!(is_update_valid(&update, rules));
-    update
 }
 
-#[aoc(day5, part2)]
-fn part2((rules, updates): &Input) -> u32 {
+fn print_analysis(input: &Input) {
+    print!("{}", analyze(input));
+}
+
+const ANALYZE: Param<bool> = Param::new("analyze", false);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&ANALYZE];
+
+#[aoc(day5, part1)]
+fn part1(input: &Input) -> u32 {
+    if ANALYZE.get() {
+        print_analysis(input);
+    }
+    let (rules, updates) = input;
+    let index = RuleIndex::new(rules);
     updates
         .iter()
-        .filter(|update| !is_update_valid(update, rules))
-        .map(|update| fix_update(update, rules))
+        .filter(|update| index.is_update_valid(update))
         .map(|update| update[update.len() / 2])
         .sum()
 }
 
+#[aoc(day5, part2)]
+fn part2(input: &Input) -> Result<u32, CycleError> {
+    if ANALYZE.get() {
+        print_analysis(input);
+    }
+    let (rules, updates) = input;
+    let index = RuleIndex::new(rules);
+    let mut sum = 0;
+    for update in updates {
+        if !index.is_update_valid(update) {
+            let update = index.fix_update(update)?;
+            debug_assert!(index.is_update_valid(&update));
+            sum += update[update.len() / 2];
+        }
+    }
+    Ok(sum)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -95,6 +345,116 @@ mod tests {
 
     #[test]
     fn part2_example() {
-        assert_eq!(part2(&parse(EXAMPLE)), 123);
+        assert_eq!(part2(&parse(EXAMPLE)), Ok(123));
+    }
+
+    #[test]
+    fn fix_update_example() {
+        let (rules, _) = parse(EXAMPLE);
+        let index = RuleIndex::new(&rules);
+        assert_eq!(
+            index.fix_update(&vec![75, 97, 47, 61, 53]),
+            Ok(vec![97, 75, 47, 61, 53])
+        );
+        assert_eq!(index.fix_update(&vec![61, 13, 29]), Ok(vec![61, 29, 13]));
+        assert_eq!(
+            index.fix_update(&vec![97, 13, 75, 29, 47]),
+            Ok(vec![97, 75, 47, 29, 13])
+        );
+    }
+
+    #[test]
+    fn analyze_example() {
+        let input = parse(EXAMPLE);
+        let analysis = analyze(&input);
+        assert_eq!(
+            analysis
+                .redundant
+                .iter()
+                .map(Rule::to_string)
+                .collect::<Vec<_>>(),
+            [
+                "47|53", "97|13", "97|61", "97|47", "75|29", "61|13", "75|53", "97|29", "97|53",
+                "61|29", "47|13", "75|61", "47|29", "75|13", "53|13"
+            ]
+        );
+        assert_eq!(analysis.contradictions, []);
+        assert_eq!(analysis.cycles, Vec::<Vec<u32>>::new());
+        assert_eq!(analysis.unconstrained, []);
+        assert_eq!(
+            analysis
+                .violations
+                .iter()
+                .map(|(i, broken)| (*i, broken.iter().map(Rule::to_string).join(" ")))
+                .collect::<Vec<_>>(),
+            [
+                (3, "97|75".to_string()),
+                (4, "29|13".to_string()),
+                (5, "29|13 47|13 47|29 75|13".to_string())
+            ]
+        );
+        print_analysis(&input);
+    }
+
+    #[test]
+    fn analyze_conflicts() {
+        let analysis = analyze(&parse("1|2\n2|3\n1|3\n3|2\n\n1,2,3,4\n5,3"));
+        assert_eq!(
+            analysis.redundant,
+            [
+                Rule {
+                    before: 1,
+                    after: 2
+                },
+                Rule {
+                    before: 1,
+                    after: 3
+                }
+            ]
+        );
+        assert_eq!(analysis.contradictions, [(2, 3)]);
+        assert_eq!(analysis.cycles, [vec![2, 3]]);
+        assert_eq!(analysis.unconstrained, [4, 5]);
+        assert_eq!(analysis.violations.len(), 1);
+        assert_eq!(analysis.violations[0].0, 0);
+    }
+
+    #[test]
+    fn analyze_cycles() {
+        let input = parse("1|2\n2|3\n3|1\n4|1\n5|6\n6|5\n7|7\n\n4,3,2,1");
+        let analysis = analyze(&input);
+        assert_eq!(analysis.contradictions, [(5, 6)]);
+        assert_eq!(analysis.cycles, [vec![1, 2, 3], vec![5, 6], vec![7]]);
+        assert!(analysis
+            .to_string()
+            .contains("cycles: 3\n  between pages 1, 2, 3\n"));
+    }
+
+    #[test]
+    fn fix_update_after_cycle() {
+        let (rules, _) = parse("1|2\n2|1\n2|3\n\n1,2,3");
+        let index = RuleIndex::new(&rules);
+        assert_eq!(
+            index.fix_update(&vec![1, 2, 3]),
+            Err(CycleError { pages: vec![1, 2] })
+        );
+    }
+
+    #[test]
+    fn fix_update_cycle() {
+        let (rules, _) = parse("1|2\n2|3\n3|1\n4|1\n\n4,3,2,1");
+        let index = RuleIndex::new(&rules);
+        assert_eq!(
+            index.fix_update(&vec![4, 3, 2, 1]),
+            Err(CycleError {
+                pages: vec![3, 2, 1]
+            })
+        );
+        assert_eq!(
+            part2(&(rules, vec![vec![4, 3, 2, 1]]))
+                .unwrap_err()
+                .to_string(),
+            "rules contain a cycle between pages 3, 2, 1"
+        );
     }
 }
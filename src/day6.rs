@@ -2,33 +2,60 @@ use crate::util::{Direction, Vector2D};
 use aoc_runner_derive::{aoc, aoc_generator};
 use std::collections::HashSet;
 
-#[derive(Debug, Clone)]
+#[derive(Debug)]
 struct Map {
     width: i32,
     height: i32,
     obstacles: HashSet<Vector2D>,
 }
 
-type Input = (Map, Vector2D);
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct Guard {
+    pos: Vector2D,
+    dir: Direction,
+}
+
+impl Guard {
+    fn glyph(&self) -> char {
+        match self.dir {
+            Direction::N => '^',
+            Direction::E => '>',
+            Direction::S => 'v',
+            Direction::W => '<',
+        }
+    }
+}
+
+impl Map {
+    fn is_in_bounds(&self, pos: Vector2D) -> bool {
+        (0..self.width).contains(&pos.x()) && (0..self.height).contains(&pos.y())
+    }
+}
+
+type Input = (Map, Vec<Guard>);
 
 #[aoc_generator(day6)]
 fn parse(input: &str) -> Input {
     let height = input.lines().count() as i32;
     let width = input.lines().next().unwrap().len() as i32;
     let mut obstacles = HashSet::new();
-    let mut guard = None;
+    let mut guards = Vec::new();
     for (y, line) in input.lines().enumerate() {
         for (x, c) in line.chars().enumerate() {
-            match c {
+            let pos = Vector2D::new(x as i32, y as i32);
+            let dir = match c {
                 '#' => {
-                    obstacles.insert(Vector2D::new(x as i32, y as i32));
-                }
-                '.' => {}
-                '^' => {
-                    guard = Some(Vector2D::new(x as i32, y as i32));
+                    obstacles.insert(pos);
+                    continue;
                 }
+                '.' => continue,
+                '^' => Direction::N,
+                '>' => Direction::E,
+                'v' => Direction::S,
+                '<' => Direction::W,
                 c => panic!("unknown char: {}", c),
             };
+            guards.push(Guard { pos, dir });
         }
     }
     let map = Map {
@@ -36,17 +63,26 @@ fn parse(input: &str) -> Input {
         height,
         obstacles,
     };
-    (map, guard.expect("no guard found"))
+    assert!(!guards.is_empty(), "no guard found");
+    (map, guards)
 }
 
-fn guard_path(map: &Map, mut guard: Vector2D) -> Option<HashSet<(Vector2D, Direction)>> {
-    let (width, height) = (map.width, map.height);
-    let mut dir = Direction::N;
+/// Where a guard goes, until it leaves the map or gets stuck in a loop.
+struct Patrol {
+    path: HashSet<(Vector2D, Direction)>,
+    looped: bool,
+}
+
+fn patrol(map: &Map, guard: Guard) -> Patrol {
+    let Guard {
+        pos: mut guard,
+        mut dir,
+    } = guard;
     let mut path = HashSet::new();
-    while (0..width).contains(&guard.x()) && (0..height).contains(&guard.y()) {
+    while map.is_in_bounds(guard) {
         let state = (guard, dir);
         if path.contains(&state) {
-            return None;
+            return Patrol { path, looped: true };
         }
         path.insert(state);
         let next_guard = guard + dir.step();
@@ -56,49 +92,212 @@ fn guard_path(map: &Map, mut guard: Vector2D) -> Option<HashSet<(Vector2D, Direc
             guard = next_guard;
         }
     }
-    Some(path)
+    Patrol {
+        path,
+        looped: false,
+    }
 }
 
-#[aoc(day6, part1)]
-fn part1((map, guard): &Input) -> usize {
-    let path = guard_path(map, *guard).unwrap();
-    let visited = path.into_iter().map(|(pos, _)| pos).collect::<HashSet<_>>();
-    visited.len()
+fn direction_index(dir: Direction) -> usize {
+    match dir {
+        Direction::N => 0,
+        Direction::E => 1,
+        Direction::S => 2,
+        Direction::W => 3,
+    }
 }
 
-#[aoc(day6, part2)]
-fn part2((map, guard): &Input) -> usize {
-    let path = guard_path(map, *guard).unwrap();
-    let mut loops = 0usize;
-    let mut seen = HashSet::new();
-    // New obstacle must be "seen" along the original path
-    for (pos, dir) in path {
-        let pos = pos + dir.step();
-        if seen.contains(&pos) {
-            // Already tested
-            continue;
+/// For every cell and direction, the cell where the guard stops before hitting the next obstacle,
+/// or `None` if the guard walks off the map.
+struct JumpTable {
+    width: i32,
+    stops: [Vec<Option<Vector2D>>; 4],
+}
+
+impl JumpTable {
+    fn new(map: &Map) -> Self {
+        let (width, height) = (map.width, map.height);
+        let mut stops = [(); 4].map(|_| vec![None; (width * height) as usize]);
+        for dir in [Direction::N, Direction::E, Direction::S, Direction::W] {
+            let step = dir.step();
+            let table = &mut stops[direction_index(dir)];
+            // Visit cells such that the next cell in this direction is always visited first
+            let xs = if step.x() > 0 {
+                (0..width).rev().collect::<Vec<_>>()
+            } else {
+                (0..width).collect()
+            };
+            let ys = if step.y() > 0 {
+                (0..height).rev().collect::<Vec<_>>()
+            } else {
+                (0..height).collect()
+            };
+            for &y in &ys {
+                for &x in &xs {
+                    let pos = Vector2D::new(x, y);
+                    let next = pos + step;
+                    table[(y * width + x) as usize] = if map.obstacles.contains(&next) {
+                        Some(pos)
+                    } else if (0..width).contains(&next.x()) && (0..height).contains(&next.y()) {
+                        table[(next.y() * width + next.x()) as usize]
+                    } else {
+                        None
+                    };
+                }
+            }
         }
-        seen.insert(pos);
-        if &pos == guard {
-            // New obstacle cannot be at guard's initial position
-            continue;
+        Self { width, stops }
+    }
+
+    fn stop(&self, pos: Vector2D, dir: Direction) -> Option<Vector2D> {
+        self.stops[direction_index(dir)][(pos.y() * self.width + pos.x()) as usize]
+    }
+
+    /// Like [`stop`](Self::stop), but with one extra obstacle inserted.
+    fn stop_with(&self, pos: Vector2D, dir: Direction, obstacle: Vector2D) -> Option<Vector2D> {
+        let stop = self.stop(pos, dir);
+        let step = dir.step();
+        let offset = obstacle - pos;
+        // Distance to the new obstacle, if it lies ahead of the guard
+        let distance = if step.x() == 0 {
+            (offset.x() == 0).then_some(offset.y() * step.y())
+        } else {
+            (offset.y() == 0).then_some(offset.x() * step.x())
         }
-        if map.obstacles.contains(&pos) {
-            // New obstacle cannot be at existing obstacle
+        .filter(|&distance| distance > 0);
+        match (distance, stop) {
+            (Some(distance), Some(stop)) if distance <= (stop - pos).manhattan_distance() => {
+                Some(obstacle - step)
+            }
+            (Some(_), None) => Some(obstacle - step),
+            _ => stop,
+        }
+    }
+
+    /// Check whether the guard gets stuck in a loop after inserting the given obstacle.
+    /// This only visits the positions where the guard turns.
+    fn has_loop_with(&self, guard: Guard, obstacle: Vector2D) -> bool {
+        let Guard {
+            pos: mut guard,
+            mut dir,
+        } = guard;
+        let mut turns = HashSet::new();
+        while let Some(stop) = self.stop_with(guard, dir, obstacle) {
+            if !turns.insert((stop, dir)) {
+                return true;
+            }
+            guard = stop;
+            dir = dir.rotate_right();
+        }
+        false
+    }
+}
+
+/// All cells visited by any guard, including guards that are stuck in a loop from the start.
+fn visited(map: &Map, guards: &[Guard]) -> HashSet<Vector2D> {
+    guards
+        .iter()
+        .flat_map(|&guard| patrol(map, guard).path)
+        .map(|(pos, _)| pos)
+        .collect()
+}
+
+/// All positions where a new obstacle would get at least one guard stuck in a loop.
+/// Guards that are already stuck in a loop are ignored.
+fn loop_obstacles(map: &Map, guards: &[Guard]) -> HashSet<Vector2D> {
+    let jump_table = JumpTable::new(map);
+    let mut loops = HashSet::new();
+    let mut seen = HashSet::new();
+    for &guard in guards {
+        let Patrol { path, looped } = patrol(map, guard);
+        if looped {
             continue;
         }
-        let mut map = map.clone();
-        map.obstacles.insert(pos);
-        if guard_path(&map, *guard).is_none() {
-            loops += 1;
+        // New obstacle must be "seen" along the original path
+        for (pos, dir) in path {
+            let pos = pos + dir.step();
+            if !map.is_in_bounds(pos) {
+                // New obstacle must be on the map (not where the guard leaves it)
+                continue;
+            }
+            if !seen.insert((guard.pos, pos)) {
+                // Already tested
+                continue;
+            }
+            if guards.iter().any(|guard| guard.pos == pos) {
+                // New obstacle cannot be at a guard's initial position
+                continue;
+            }
+            if map.obstacles.contains(&pos) {
+                // New obstacle cannot be at existing obstacle
+                continue;
+            }
+            if jump_table.has_loop_with(guard, pos) {
+                loops.insert(pos);
+            }
         }
     }
     loops
 }
 
+/// Draw the paths of the guards like in the puzzle description,
+/// with every new obstacle that would cause a loop marked as `O`.
+fn render(map: &Map, guards: &[Guard]) -> String {
+    let mut vertical = HashSet::new();
+    let mut horizontal = HashSet::new();
+    for &guard in guards {
+        for (pos, dir) in patrol(map, guard).path {
+            match dir {
+                Direction::N | Direction::S => vertical.insert(pos),
+                Direction::E | Direction::W => horizontal.insert(pos),
+            };
+        }
+    }
+    let loops = loop_obstacles(map, guards);
+    let mut result = String::new();
+    for y in 0..map.height {
+        for x in 0..map.width {
+            let pos = Vector2D::new(x, y);
+            let c = if map.obstacles.contains(&pos) {
+                '#'
+            } else if loops.contains(&pos) {
+                'O'
+            } else if let Some(guard) = guards.iter().find(|guard| guard.pos == pos) {
+                guard.glyph()
+            } else {
+                match (vertical.contains(&pos), horizontal.contains(&pos)) {
+                    (true, true) => '+',
+                    (true, false) => '|',
+                    (false, true) => '-',
+                    (false, false) => '.',
+                }
+            };
+            result.push(c);
+        }
+        result.push('\n');
+    }
+    result
+}
+
+#[allow(unused)]
+fn print_path(map: &Map, guards: &[Guard]) {
+    print!("{}", render(map, guards));
+}
+
+#[aoc(day6, part1)]
+fn part1((map, guards): &Input) -> usize {
+    visited(map, guards).len()
+}
+
+#[aoc(day6, part2)]
+fn part2((map, guards): &Input) -> usize {
+    loop_obstacles(map, guards).len()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
 
     const EXAMPLE: &str = include_str!("../examples/2024/day6.txt");
 
@@ -111,4 +310,156 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 6);
     }
+
+    /// Count loops by cloning the map and re-simulating every step.
+    fn part2_naive((map, guards): &Input) -> usize {
+        let candidates = guards
+            .iter()
+            .map(|&guard| patrol(map, guard))
+            .filter(|patrol| !patrol.looped)
+            .flat_map(|patrol| patrol.path)
+            .map(|(pos, dir)| pos + dir.step())
+            .filter(|&pos| map.is_in_bounds(pos))
+            .filter(|pos| {
+                !guards.iter().any(|guard| guard.pos == *pos) && !map.obstacles.contains(pos)
+            })
+            .collect::<HashSet<_>>();
+        let original = map;
+        candidates
+            .into_iter()
+            .filter(|&pos| {
+                let mut obstacles = map.obstacles.clone();
+                obstacles.insert(pos);
+                let map = Map {
+                    width: map.width,
+                    height: map.height,
+                    obstacles,
+                };
+                guards
+                    .iter()
+                    .filter(|&&guard| !patrol(original, guard).looped)
+                    .any(|&guard| patrol(&map, guard).looped)
+            })
+            .count()
+    }
+
+    #[test]
+    fn jump_table_example() {
+        let (map, guards) = parse(EXAMPLE);
+        let guard = guards[0];
+        let jump_table = JumpTable::new(&map);
+        assert_eq!(
+            jump_table.stop(guard.pos, Direction::N),
+            Some(Vector2D::new(4, 1))
+        );
+        assert_eq!(
+            jump_table.stop(Vector2D::new(4, 1), Direction::E),
+            Some(Vector2D::new(8, 1))
+        );
+        assert_eq!(jump_table.stop(Vector2D::new(7, 9), Direction::S), None);
+        let obstacle = Vector2D::new(3, 6);
+        assert_eq!(
+            jump_table.stop_with(Vector2D::new(8, 6), Direction::W, obstacle),
+            Some(Vector2D::new(4, 6))
+        );
+        assert!(jump_table.has_loop_with(guard, obstacle));
+        assert!(!jump_table.has_loop_with(guard, Vector2D::new(0, 0)));
+        assert_eq!(part2_naive(&parse(EXAMPLE)), 6);
+    }
+
+    #[test]
+    fn render_example() {
+        let (map, guards) = parse(EXAMPLE);
+        assert_eq!(
+            render(&map, &guards),
+            "\
+....#.....
+....+---+#
+....|...|.
+..#.|...|.
+..+-+-+#|.
+..|.|.|.|.
+.#+O^-+-+.
+.+----OO#.
+#O-O--+|..
+......#O..
+"
+        );
+        print_path(&map, &guards);
+    }
+
+    const EXAMPLE_GUARDS: &str = "\
+.#..
+>..#
+....
+#.<.";
+
+    #[test]
+    fn multiple_guards() {
+        let (map, guards) = parse(EXAMPLE_GUARDS);
+        assert_eq!(guards.iter().map(Guard::glyph).collect::<String>(), "><");
+        assert_eq!(part1(&(map, guards)), 7);
+        assert_eq!(
+            part2(&parse(EXAMPLE_GUARDS)),
+            part2_naive(&parse(EXAMPLE_GUARDS))
+        );
+    }
+
+    #[test]
+    fn obstacle_outside_map() {
+        // The guard's exit step would loop if an obstacle could be placed there
+        let input = parse("#.>#...\n#......\n..#....\n.###..#");
+        assert_eq!(loop_obstacles(&input.0, &input.1), HashSet::new());
+        assert_eq!(part2(&input), 0);
+        assert_eq!(part2_naive(&input), 0);
+    }
+
+    const EXAMPLE_STUCK: &str = "\
+.#...
+.^.#.
+#....
+..#..
+....>";
+
+    #[test]
+    fn guard_stuck_from_start() {
+        let (map, guards) = parse(EXAMPLE_STUCK);
+        assert!(patrol(&map, guards[0]).looped);
+        assert!(!patrol(&map, guards[1]).looped);
+        assert_eq!(part1(&(map, guards)), 5);
+        assert_eq!(
+            part2(&parse(EXAMPLE_STUCK)),
+            part2_naive(&parse(EXAMPLE_STUCK))
+        );
+        let (map, guards) = parse(EXAMPLE_STUCK);
+        assert_eq!(
+            render(&map, &guards),
+            "\
+.#...
+.^+#.
+#++..
+..#..
+....>
+"
+        );
+    }
+
+    /// Compare against the naive approach on the real input:
+    /// `cargo test --release day6::tests::bench_part2 -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_part2() {
+        let Some(input) = crate::input::load_or_skip(6) else {
+            return;
+        };
+        let input = parse(input.trim_end());
+        let start = Instant::now();
+        let naive = part2_naive(&input);
+        let naive_time = start.elapsed();
+        let start = Instant::now();
+        let fast = part2(&input);
+        let fast_time = start.elapsed();
+        assert_eq!(naive, fast);
+        println!("naive: {naive_time:?}, jump table: {fast_time:?}");
+    }
 }
@@ -1,4 +1,8 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
+use itertools::Itertools;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 #[derive(Debug, Clone)]
 struct Equation {
@@ -19,52 +23,412 @@ fn parse(input: &str) -> Vec<Equation> {
         .collect()
 }
 
-fn solve(equation: &Equation, part2: bool) -> bool {
-    fn inner(result: i64, current: i64, values: &[i64], part2: bool) -> bool {
-        if current > result {
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Operator {
+    Add,
+    Subtract,
+    Multiply,
+    /// Integer division, rounding towards zero.
+    Divide,
+    Power,
+    Concatenate,
+}
+
+impl Operator {
+    const ALL: [Operator; 6] = [
+        Operator::Add,
+        Operator::Subtract,
+        Operator::Multiply,
+        Operator::Divide,
+        Operator::Power,
+        Operator::Concatenate,
+    ];
+
+    /// Apply the operator, or return `None` if the result is undefined or overflows.
+    fn apply(self, left: i64, right: i64) -> Option<i64> {
+        match self {
+            Operator::Add => left.checked_add(right),
+            Operator::Subtract => left.checked_sub(right),
+            Operator::Multiply => left.checked_mul(right),
+            Operator::Divide => left.checked_div(right),
+            Operator::Power => left.checked_pow(right.try_into().ok()?),
+            Operator::Concatenate => concatenate(left, right),
+        }
+    }
+
+    /// Find the left operand that gives the result with the given right operand,
+    /// or `None` if there is no such operand.
+    ///
+    /// Only valid for [invertible](Self::is_invertible) operators, and for multiplication
+    /// only with a non-zero right operand.
+    fn unapply(self, result: i64, right: i64) -> Option<i64> {
+        match self {
+            Operator::Add => result.checked_sub(right),
+            Operator::Subtract => result.checked_add(right),
+            // No remainder for `i64::MIN % -1` (which overflows) or division by zero
+            Operator::Multiply => (result.checked_rem(right)? == 0).then(|| result / right),
+            Operator::Concatenate => {
+                if result < 0 || right < 0 {
+                    return None;
+                }
+                // The result must end with the digits of the right operand
+                let shift = 10_i64.pow(right.checked_ilog10().unwrap_or(0) + 1);
+                (result % shift == right).then(|| result / shift)
+            }
+            Operator::Divide | Operator::Power => {
+                unreachable!("operator {self} cannot be inverted")
+            }
+        }
+    }
+
+    /// Whether the left operand can be uniquely determined from the result and the right operand.
+    fn is_invertible(self) -> bool {
+        !matches!(self, Operator::Divide | Operator::Power)
+    }
+
+    /// Whether the result is never smaller than the left operand, given positive operands.
+    fn is_increasing(self) -> bool {
+        matches!(
+            self,
+            Operator::Add | Operator::Multiply | Operator::Power | Operator::Concatenate
+        )
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Operator::Add => "+",
+            Operator::Subtract => "-",
+            Operator::Multiply => "*",
+            Operator::Divide => "/",
+            Operator::Power => "^",
+            Operator::Concatenate => "||",
+        }
+    }
+}
+
+impl Display for Operator {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.symbol())
+    }
+}
+
+/// A set of operators, written as their symbols (e.g. `+*||`).
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct OperatorSet(u8);
+
+impl OperatorSet {
+    const fn new(operators: &[Operator]) -> Self {
+        let mut set = 0;
+        let mut i = 0;
+        while i < operators.len() {
+            set |= 1 << operators[i] as u8;
+            i += 1;
+        }
+        Self(set)
+    }
+
+    fn iter(self) -> impl Iterator<Item = Operator> {
+        Operator::ALL
+            .into_iter()
+            .filter(move |&op| self.0 & (1 << op as u8) != 0)
+    }
+}
+
+impl FromStr for OperatorSet {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut operators = Vec::new();
+        let mut rest = s.trim();
+        while !rest.is_empty() {
+            let op = Operator::ALL
+                .into_iter()
+                .find(|op| rest.starts_with(op.symbol()))
+                .ok_or_else(|| format!("unknown operator: {rest}"))?;
+            operators.push(op);
+            rest = rest[op.symbol().len()..].trim_start();
+        }
+        Ok(OperatorSet::new(&operators))
+    }
+}
+
+impl Display for OperatorSet {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        for op in self.iter() {
+            f.write_str(op.symbol())?;
+        }
+        Ok(())
+    }
+}
+
+/// Find operators to put between the values (evaluated left to right) to get the test value.
+fn solve(equation: &Equation, operators: OperatorSet) -> Option<Vec<Operator>> {
+    if can_solve_backward(equation, operators) {
+        solve_backward(equation, operators)
+    } else {
+        solve_forward(equation, operators)
+    }
+}
+
+/// Count all assignments of operators that give the test value.
+fn count_solutions(equation: &Equation, operators: OperatorSet) -> u64 {
+    if can_solve_backward(equation, operators) {
+        count_backward(equation, operators)
+    } else {
+        count_forward(equation, operators)
+    }
+}
+
+/// Whether all operators need to be tried, or only those that stay below the test value.
+fn can_prune(equation: &Equation, operators: OperatorSet) -> bool {
+    operators.iter().all(Operator::is_increasing) && equation.values.iter().all(|&value| value > 0)
+}
+
+fn solve_forward(equation: &Equation, operators: OperatorSet) -> Option<Vec<Operator>> {
+    fn inner(
+        result: i64,
+        current: i64,
+        values: &[i64],
+        operators: OperatorSet,
+        can_prune: bool,
+        solution: &mut Vec<Operator>,
+    ) -> bool {
+        if can_prune && current > result {
             // Temporary result cannot exceed final result,
-            // since there's no subtract or divide operators.
+            // since no operator makes it smaller.
             return false;
         }
-        let Some((next, values)) = values.split_first() else {
+        let Some((&next, values)) = values.split_first() else {
             // All values used, do we have the right result now?
             return current == result;
         };
         // Try all operators
-        inner(result, current + next, values, part2)
-            || inner(result, current * next, values, part2)
-            || (part2 && inner(result, concatenate(current, *next), values, part2))
+        for op in operators.iter() {
+            let Some(current) = op.apply(current, next) else {
+                continue;
+            };
+            solution.push(op);
+            if inner(result, current, values, operators, can_prune, solution) {
+                return true;
+            }
+            solution.pop();
+        }
+        false
     }
 
-    inner(equation.test_value, 0, &equation.values, part2)
+    let (&first, values) = equation.values.split_first()?;
+    let can_prune = can_prune(equation, operators);
+    let mut solution = Vec::with_capacity(values.len());
+    inner(
+        equation.test_value,
+        first,
+        values,
+        operators,
+        can_prune,
+        &mut solution,
+    )
+    .then_some(solution)
 }
 
-#[aoc(day7, part1)]
-fn part1(input: &[Equation]) -> i64 {
+fn count_forward(equation: &Equation, operators: OperatorSet) -> u64 {
+    fn inner(
+        result: i64,
+        current: i64,
+        values: &[i64],
+        operators: OperatorSet,
+        can_prune: bool,
+    ) -> u64 {
+        if can_prune && current > result {
+            return 0;
+        }
+        let Some((&next, values)) = values.split_first() else {
+            return (current == result) as u64;
+        };
+        operators
+            .iter()
+            .filter_map(|op| op.apply(current, next))
+            .map(|current| inner(result, current, values, operators, can_prune))
+            .sum()
+    }
+
+    let Some((&first, values)) = equation.values.split_first() else {
+        return 0;
+    };
+    inner(
+        equation.test_value,
+        first,
+        values,
+        operators,
+        can_prune(equation, operators),
+    )
+}
+
+/// Whether the equation can be solved backwards, by undoing operators starting from the test value.
+fn can_solve_backward(equation: &Equation, operators: OperatorSet) -> bool {
+    operators.iter().all(Operator::is_invertible)
+        && !(operators.iter().contains(&Operator::Multiply)
+            && equation.values.iter().skip(1).contains(&0))
+}
+
+/// Solve by peeling off values from the end, undoing each operator.
+/// This prunes much earlier than solving forwards, since most operators can only be undone
+/// if the remaining result is divisible by (or ends with) the last value.
+fn solve_backward(equation: &Equation, operators: OperatorSet) -> Option<Vec<Operator>> {
+    fn inner(
+        result: i64,
+        values: &[i64],
+        operators: OperatorSet,
+        can_prune: bool,
+        solution: &mut Vec<Operator>,
+    ) -> bool {
+        if can_prune && result <= 0 {
+            // All intermediate results must be positive
+            return false;
+        }
+        let Some((&last, values)) = values.split_last() else {
+            unreachable!()
+        };
+        if values.is_empty() {
+            return result == last;
+        }
+        for op in operators.iter() {
+            let Some(result) = op.unapply(result, last) else {
+                continue;
+            };
+            solution.push(op);
+            if inner(result, values, operators, can_prune, solution) {
+                return true;
+            }
+            solution.pop();
+        }
+        false
+    }
+
+    debug_assert!(can_solve_backward(equation, operators));
+    if equation.values.is_empty() {
+        return None;
+    }
+    let can_prune = can_prune(equation, operators);
+    let mut solution = Vec::with_capacity(equation.values.len() - 1);
+    if !inner(
+        equation.test_value,
+        &equation.values,
+        operators,
+        can_prune,
+        &mut solution,
+    ) {
+        return None;
+    }
+    // Operators were found from right to left
+    solution.reverse();
+    Some(solution)
+}
+
+fn count_backward(equation: &Equation, operators: OperatorSet) -> u64 {
+    fn inner(result: i64, values: &[i64], operators: OperatorSet, can_prune: bool) -> u64 {
+        if can_prune && result <= 0 {
+            return 0;
+        }
+        let Some((&last, values)) = values.split_last() else {
+            unreachable!()
+        };
+        if values.is_empty() {
+            return (result == last) as u64;
+        }
+        operators
+            .iter()
+            .filter_map(|op| op.unapply(result, last))
+            .map(|result| inner(result, values, operators, can_prune))
+            .sum()
+    }
+
+    debug_assert!(can_solve_backward(equation, operators));
+    if equation.values.is_empty() {
+        return 0;
+    }
+    inner(
+        equation.test_value,
+        &equation.values,
+        operators,
+        can_prune(equation, operators),
+    )
+}
+
+/// Write out an equation with the given operators, e.g. `292 = 11 + 6 * 16 + 20`.
+fn format_solution(equation: &Equation, solution: &[Operator]) -> String {
+    let mut result = format!("{} = {}", equation.test_value, equation.values[0]);
+    for (op, value) in solution.iter().zip(&equation.values[1..]) {
+        result += &format!(" {op} {value}");
+    }
+    result
+}
+
+fn print_solutions(input: &[Equation], operators: OperatorSet) {
+    for equation in input {
+        match solve(equation, operators) {
+            Some(solution) => println!(
+                "{} ({} solutions)",
+                format_solution(equation, &solution),
+                count_solutions(equation, operators)
+            ),
+            None => println!(
+                "{} != {}",
+                equation.test_value,
+                equation.values.iter().join(" ? ")
+            ),
+        }
+    }
+}
+
+const OPERATORS_PART1: Param<OperatorSet> = Param::for_part(
+    1,
+    "operators",
+    OperatorSet::new(&[Operator::Add, Operator::Multiply]),
+);
+const OPERATORS_PART2: Param<OperatorSet> = Param::for_part(
+    2,
+    "operators",
+    OperatorSet::new(&[Operator::Add, Operator::Multiply, Operator::Concatenate]),
+);
+
+const EXPLAIN: Param<bool> = Param::new("explain", false);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&OPERATORS_PART1, &OPERATORS_PART2, &EXPLAIN];
+
+fn total_calibration(input: &[Equation], operators: OperatorSet) -> i64 {
+    if EXPLAIN.get() {
+        print_solutions(input, operators);
+    }
     input
         .iter()
-        .filter(|equation| solve(equation, false))
+        .filter(|equation| solve(equation, operators).is_some())
         .map(|equation| equation.test_value)
         .sum()
 }
 
-fn concatenate(left: i64, right: i64) -> i64 {
-    let right_digits = right.ilog10() + 1;
-    (left * 10_i64.pow(right_digits)) + right
+#[aoc(day7, part1)]
+fn part1(input: &[Equation]) -> i64 {
+    total_calibration(input, OPERATORS_PART1.get())
+}
+
+fn concatenate(left: i64, right: i64) -> Option<i64> {
+    if left < 0 || right < 0 {
+        return None;
+    }
+    let right_digits = right.checked_ilog10().unwrap_or(0) + 1;
+    left.checked_mul(10_i64.pow(right_digits))?
+        .checked_add(right)
 }
 
 #[aoc(day7, part2)]
 fn part2(input: &[Equation]) -> i64 {
-    input
-        .iter()
-        .filter(|equation| solve(equation, true))
-        .map(|equation| equation.test_value)
-        .sum()
+    total_calibration(input, OPERATORS_PART2.get())
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::time::Instant;
 
     const EXAMPLE: &str = include_str!("../examples/2024/day7.txt");
 
@@ -77,4 +441,166 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 11387);
     }
+
+    #[test]
+    fn operator_set() {
+        let operators = "+ * ||".parse::<OperatorSet>().unwrap();
+        assert_eq!(operators, OPERATORS_PART2.get());
+        assert_eq!(operators.to_string(), "+*||");
+        assert_eq!(OPERATORS_PART1.get().to_string(), "+*");
+        assert_eq!(
+            "-/^"
+                .parse::<OperatorSet>()
+                .unwrap()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Operator::Subtract, Operator::Divide, Operator::Power]
+        );
+        assert!("+%".parse::<OperatorSet>().is_err());
+    }
+
+    #[test]
+    fn unapply_edge_cases() {
+        assert_eq!(Operator::Multiply.unapply(i64::MIN, -1), None);
+        assert_eq!(Operator::Multiply.unapply(12, 0), None);
+        assert_eq!(Operator::Multiply.unapply(-12, -3), Some(4));
+        assert_eq!(Operator::Subtract.unapply(-5, -3), Some(-8));
+        assert_eq!(Operator::Concatenate.unapply(-156, 6), None);
+    }
+
+    #[test]
+    fn solve_example() {
+        let input = parse(EXAMPLE);
+        let operators = OPERATORS_PART2.get();
+        let solutions = input
+            .iter()
+            .filter_map(|equation| {
+                let solution = solve(equation, operators)?;
+                Some(format_solution(equation, &solution))
+            })
+            .collect::<Vec<_>>();
+        assert_eq!(
+            solutions,
+            vec![
+                "190 = 10 * 19",
+                "3267 = 81 * 40 + 27",
+                "156 = 15 || 6",
+                "7290 = 6 * 8 || 6 * 15",
+                "192 = 17 || 8 + 14",
+                "292 = 11 + 6 * 16 + 20",
+            ]
+        );
+        print_solutions(&input, operators);
+    }
+
+    #[test]
+    fn solve_backward_example() {
+        let input = parse(EXAMPLE);
+        for operators in [OPERATORS_PART1.get(), OPERATORS_PART2.get()] {
+            for equation in &input {
+                assert!(can_solve_backward(equation, operators));
+                let forward = solve_forward(equation, operators);
+                let backward = solve_backward(equation, operators);
+                assert_eq!(forward.is_some(), backward.is_some());
+                if let Some(solution) = backward {
+                    let value = equation.values[1..]
+                        .iter()
+                        .zip(&solution)
+                        .try_fold(equation.values[0], |current, (&next, op)| {
+                            op.apply(current, next)
+                        });
+                    assert_eq!(value, Some(equation.test_value));
+                }
+                assert_eq!(
+                    count_forward(equation, operators),
+                    count_backward(equation, operators)
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn count_example() {
+        let input = parse(EXAMPLE);
+        let counts = input
+            .iter()
+            .map(|equation| count_solutions(equation, OPERATORS_PART2.get()))
+            .collect::<Vec<_>>();
+        assert_eq!(counts, vec![1, 2, 0, 1, 1, 0, 1, 0, 1]);
+        let equation = Equation {
+            test_value: 4,
+            values: vec![2, 2, 1, 0],
+        };
+        let operators = "+-*||".parse().unwrap();
+        assert!(!can_solve_backward(&equation, operators));
+        assert_eq!(
+            count_solutions(&equation, operators),
+            count_forward(&equation, operators)
+        );
+        let equation = Equation {
+            test_value: 4,
+            values: vec![2, 2, 1, 1],
+        };
+        assert_eq!(
+            count_backward(&equation, operators),
+            count_forward(&equation, operators)
+        );
+    }
+
+    /// Compare against solving forwards on the real input:
+    /// `cargo test --release day7::tests::bench_solvers -- --ignored --nocapture`
+    #[test]
+    #[ignore]
+    fn bench_solvers() {
+        let Some(input) = crate::input::load_or_skip(7) else {
+            return;
+        };
+        let input = parse(input.trim_end());
+        let operators = OPERATORS_PART2.get();
+        let start = Instant::now();
+        let forward = input
+            .iter()
+            .filter(|equation| solve_forward(equation, operators).is_some())
+            .count();
+        let forward_time = start.elapsed();
+        let start = Instant::now();
+        let backward = input
+            .iter()
+            .filter(|equation| solve_backward(equation, operators).is_some())
+            .count();
+        let backward_time = start.elapsed();
+        assert_eq!(forward, backward);
+        println!("solve forward: {forward_time:?}, backward: {backward_time:?}");
+        let start = Instant::now();
+        let forward = input
+            .iter()
+            .map(|equation| count_forward(equation, operators))
+            .sum::<u64>();
+        let forward_time = start.elapsed();
+        let start = Instant::now();
+        let backward = input
+            .iter()
+            .map(|equation| count_backward(equation, operators))
+            .sum::<u64>();
+        let backward_time = start.elapsed();
+        assert_eq!(forward, backward);
+        println!("count forward: {forward_time:?}, backward: {backward_time:?}");
+    }
+
+    #[test]
+    fn solve_other_operators() {
+        let equation = |test_value, values: &[i64]| Equation {
+            test_value,
+            values: values.to_vec(),
+        };
+        let operators = "+-*/^||".parse().unwrap();
+        let solve = |equation: &Equation| {
+            solve(equation, operators).map(|solution| format_solution(equation, &solution))
+        };
+        assert_eq!(solve(&equation(1, &[5, 4])).unwrap(), "1 = 5 - 4");
+        assert_eq!(solve(&equation(3, &[7, 2])).unwrap(), "3 = 7 / 2");
+        assert_eq!(solve(&equation(81, &[3, 2, 2])).unwrap(), "81 = 3 ^ 2 ^ 2");
+        assert_eq!(solve(&equation(-6, &[2, 8, 0])).unwrap(), "-6 = 2 - 8 + 0");
+        assert_eq!(solve(&equation(17, &[1, 0])), None);
+    }
 }
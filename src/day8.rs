@@ -1,7 +1,10 @@
-use crate::util::Vector2D;
+use crate::params::{Param, Parameter};
+use crate::util::{gcd, Vector2D};
 use aoc_runner_derive::{aoc, aoc_generator};
 use itertools::Itertools;
 use std::collections::HashMap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
 struct Map {
     width: i32,
@@ -35,61 +38,245 @@ fn parse(input: &str) -> Map {
     }
 }
 
+/// Where two antennas of the same frequency create antinodes.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Resonance {
+    /// In line with both antennas, where one antenna is this many times as far away as the other.
+    Ratio(i32),
+    /// At every grid position in line with both antennas, up to the given number of positions
+    /// beyond either antenna (or without limit).
+    Harmonics(Option<i32>),
+}
+
+impl FromStr for Resonance {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (kind, value) = match s.split_once(':') {
+            Some((kind, value)) => (kind, Some(value)),
+            None => (s, None),
+        };
+        let value = value
+            .map(|value| value.parse::<i32>().map_err(|e| format!("{e}: {value}")))
+            .transpose()?;
+        match (kind, value) {
+            ("ratio", Some(ratio)) if ratio >= 2 => Ok(Resonance::Ratio(ratio)),
+            ("ratio", _) => Err(format!("ratio must be at least 2: {s}")),
+            ("harmonics", Some(max)) if max < 0 => {
+                Err(format!("harmonics must not be negative: {s}"))
+            }
+            ("harmonics", max) => Ok(Resonance::Harmonics(max)),
+            _ => Err(format!("unknown resonance: {s}")),
+        }
+    }
+}
+
+impl Display for Resonance {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Resonance::Ratio(ratio) => write!(f, "ratio:{ratio}"),
+            Resonance::Harmonics(None) => write!(f, "harmonics"),
+            Resonance::Harmonics(Some(max)) => write!(f, "harmonics:{max}"),
+        }
+    }
+}
+
 impl Map {
     fn is_in_bounds(&self, pos: Vector2D) -> bool {
         (0..self.width).contains(&pos.x()) && (0..self.height).contains(&pos.y())
     }
 
-    fn antinodes(&self, part2: bool) -> impl Iterator<Item = Vector2D> + use<'_> {
+    fn antinodes(&self, resonance: Resonance) -> impl Iterator<Item = Vector2D> + use<'_> {
         self.antennas
             .values()
-            .flat_map(move |antennas| self.antinodes_for(antennas, part2))
+            .flat_map(move |antennas| self.antinodes_for(antennas, resonance))
             .unique()
     }
 
     fn antinodes_for<'a>(
         &'a self,
         antennas: &'a [Vector2D],
-        part2: bool,
+        resonance: Resonance,
     ) -> impl Iterator<Item = Vector2D> + 'a {
-        antennas.iter().combinations(2).flat_map(move |vec| {
-            let (left, right) = (*vec[0], *vec[1]);
-            let offset = right - left;
-            let mut result = vec![];
-            if part2 {
-                let mut pos = left;
-                while self.is_in_bounds(pos) {
-                    result.push(pos);
-                    pos -= offset;
-                }
-                let mut pos = right;
-                while self.is_in_bounds(pos) {
-                    result.push(pos);
-                    pos += offset;
-                }
-            } else {
-                let left_antinode = left - offset;
-                if self.is_in_bounds(left_antinode) {
-                    result.push(left_antinode);
-                }
-                let right_antinode = right + offset;
-                if self.is_in_bounds(right_antinode) {
-                    result.push(right_antinode);
+        antennas
+            .iter()
+            .tuple_combinations()
+            .flat_map(move |(&left, &right)| match resonance {
+                Resonance::Ratio(ratio) => self.ratio_antinodes(left, right, ratio),
+                Resonance::Harmonics(max) => self.harmonic_antinodes(left, right, max),
+            })
+    }
+
+    fn ratio_antinodes(&self, left: Vector2D, right: Vector2D, ratio: i32) -> Vec<Vector2D> {
+        // An antinode beyond `right` is at `right + offset / (ratio - 1)`,
+        // which only exists if that's a grid position.
+        [(left, right), (right, left)]
+            .into_iter()
+            .filter_map(|(near, far)| {
+                let offset = far - near;
+                if offset.x() % (ratio - 1) != 0 || offset.y() % (ratio - 1) != 0 {
+                    return None;
                 }
-            }
-            result
+                Some(far + offset / (ratio - 1))
+            })
+            .filter(|&pos| self.is_in_bounds(pos))
+            .collect()
+    }
+
+    fn harmonic_antinodes(
+        &self,
+        left: Vector2D,
+        right: Vector2D,
+        max: Option<i32>,
+    ) -> Vec<Vector2D> {
+        // Step to the next grid position on the line
+        let offset = right - left;
+        let steps = gcd(offset.x(), offset.y());
+        let step = offset / steps;
+        let mut result = vec![];
+        let mut pos = left;
+        let mut k = 0;
+        while self.is_in_bounds(pos) && max.is_none_or(|max| k <= max) {
+            result.push(pos);
+            pos -= step;
+            k += 1;
+        }
+        let mut pos = left + step;
+        let mut k = 1;
+        while self.is_in_bounds(pos) && max.is_none_or(|max| k <= steps + max) {
+            result.push(pos);
+            pos += step;
+            k += 1;
+        }
+        result
+    }
+}
+
+/// What to draw on top of the antenna map.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum Overlay {
+    /// Mark antinodes with `#`.
+    Antinodes,
+    /// Show how many frequencies have an antinode at each position.
+    Counts,
+}
+
+impl FromStr for Overlay {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "antinodes" => Ok(Overlay::Antinodes),
+            "counts" => Ok(Overlay::Counts),
+            _ => Err(format!("unknown overlay: {s}")),
+        }
+    }
+}
+
+impl Display for Overlay {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Overlay::Antinodes => "antinodes",
+            Overlay::Counts => "counts",
         })
     }
 }
 
+/// Which antennas to draw: all of them, or only those of a single frequency.
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+struct FrequencyFilter(Option<char>);
+
+impl FromStr for FrequencyFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut chars = s.chars();
+        match (chars.next(), chars.next()) {
+            _ if s == "all" => Ok(FrequencyFilter(None)),
+            (Some(c), None) if c.is_ascii_alphanumeric() => Ok(FrequencyFilter(Some(c))),
+            _ => Err(format!("invalid frequency: {s}")),
+        }
+    }
+}
+
+impl Display for FrequencyFilter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self.0 {
+            Some(c) => write!(f, "{c}"),
+            None => f.write_str("all"),
+        }
+    }
+}
+
+impl Map {
+    /// Draw the map like in the puzzle description, optionally for a single frequency.
+    /// Antennas are always drawn, even if there's an antinode at the same position.
+    fn render(&self, resonance: Resonance, frequency: Option<char>, overlay: Overlay) -> String {
+        let mut antennas = HashMap::<Vector2D, char>::new();
+        let mut counts = HashMap::<Vector2D, usize>::new();
+        for (&c, positions) in &self.antennas {
+            if frequency.is_some_and(|frequency| frequency != c) {
+                continue;
+            }
+            antennas.extend(positions.iter().map(|&pos| (pos, c)));
+            for pos in self.antinodes_for(positions, resonance).unique() {
+                *counts.entry(pos).or_default() += 1;
+            }
+        }
+        let mut result = String::new();
+        for y in 0..self.height {
+            for x in 0..self.width {
+                let pos = Vector2D::new(x, y);
+                let c = match (antennas.get(&pos), counts.get(&pos), overlay) {
+                    (Some(&antenna), _, _) => antenna,
+                    (None, None, _) => '.',
+                    (None, Some(_), Overlay::Antinodes) => '#',
+                    (None, Some(&count), Overlay::Counts) => {
+                        char::from_digit(count as u32, 10).unwrap_or('*')
+                    }
+                };
+                result.push(c);
+            }
+            result.push('\n');
+        }
+        result
+    }
+}
+
+fn print_map(map: &Map, resonance: Resonance, frequency: Option<char>, overlay: Overlay) {
+    print!("{}", map.render(resonance, frequency, overlay));
+}
+
+const RESONANCE_PART1: Param<Resonance> = Param::for_part(1, "resonance", Resonance::Ratio(2));
+const RESONANCE_PART2: Param<Resonance> =
+    Param::for_part(2, "resonance", Resonance::Harmonics(None));
+const RENDER: Param<bool> = Param::new("render", false);
+const FREQUENCY: Param<FrequencyFilter> = Param::new("frequency", FrequencyFilter(None));
+const OVERLAY: Param<Overlay> = Param::new("overlay", Overlay::Antinodes);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[
+    &RESONANCE_PART1,
+    &RESONANCE_PART2,
+    &RENDER,
+    &FREQUENCY,
+    &OVERLAY,
+];
+
+fn count_antinodes(map: &Map, resonance: Resonance) -> usize {
+    if RENDER.get() {
+        print_map(map, resonance, FREQUENCY.get().0, OVERLAY.get());
+    }
+    map.antinodes(resonance).count()
+}
+
 #[aoc(day8, part1)]
 fn part1(map: &Map) -> usize {
-    map.antinodes(false).count()
+    count_antinodes(map, RESONANCE_PART1.get())
 }
 
 #[aoc(day8, part2)]
 fn part2(map: &Map) -> usize {
-    map.antinodes(true).count()
+    count_antinodes(map, RESONANCE_PART2.get())
 }
 
 #[cfg(test)]
@@ -107,4 +294,162 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 34);
     }
+
+    #[test]
+    fn render_example() {
+        let map = parse(EXAMPLE);
+        assert_eq!(
+            map.render(Resonance::Ratio(2), None, Overlay::Antinodes),
+            "\
+......#....#
+...#....0...
+....#0....#.
+..#....0....
+....0....#..
+.#....A.....
+...#........
+#......#....
+........A...
+.........A..
+..........#.
+..........#.
+"
+        );
+        assert_eq!(
+            map.render(Resonance::Harmonics(None), None, Overlay::Antinodes),
+            "\
+##....#....#
+.#.#....0...
+..#.#0....#.
+..##...0....
+....0....#..
+.#...#A....#
+...#..#.....
+#....#.#....
+..#.....A...
+....#....A..
+.#........#.
+...#......##
+"
+        );
+        print_map(&map, Resonance::Ratio(2), None, Overlay::Antinodes);
+    }
+
+    const EXAMPLE_T: &str = "\
+T.........
+...T......
+.T........
+..........
+..........
+..........
+..........
+..........
+..........
+..........";
+
+    #[test]
+    fn render_frequency() {
+        let map = parse(EXAMPLE_T);
+        assert_eq!(
+            map.render(Resonance::Harmonics(None), Some('T'), Overlay::Antinodes),
+            "\
+T....#....
+...T......
+.T....#...
+.........#
+..#.......
+..........
+...#......
+..........
+....#.....
+..........
+"
+        );
+        let map = parse(EXAMPLE);
+        assert_eq!(
+            map.render(Resonance::Ratio(2), Some('A'), Overlay::Antinodes),
+            map.render(Resonance::Ratio(2), Some('A'), Overlay::Counts)
+                .replace('1', "#")
+        );
+        print_map(&map, Resonance::Harmonics(None), None, Overlay::Counts);
+    }
+
+    #[test]
+    fn parse_resonance() {
+        assert_eq!("ratio:3".parse(), Ok(Resonance::Ratio(3)));
+        assert_eq!("harmonics".parse(), Ok(Resonance::Harmonics(None)));
+        assert_eq!("harmonics:2".parse(), Ok(Resonance::Harmonics(Some(2))));
+        assert!("ratio:1".parse::<Resonance>().is_err());
+        assert!("ratio".parse::<Resonance>().is_err());
+        assert!("echo".parse::<Resonance>().is_err());
+        assert_eq!(Resonance::Harmonics(Some(2)).to_string(), "harmonics:2");
+    }
+
+    #[test]
+    fn parse_render_params() {
+        assert_eq!("counts".parse(), Ok(Overlay::Counts));
+        assert!("heatmap".parse::<Overlay>().is_err());
+        assert_eq!(Overlay::Antinodes.to_string(), "antinodes");
+        assert_eq!("all".parse(), Ok(FrequencyFilter(None)));
+        assert_eq!("A".parse(), Ok(FrequencyFilter(Some('A'))));
+        assert!("AB".parse::<FrequencyFilter>().is_err());
+        assert!("#".parse::<FrequencyFilter>().is_err());
+        assert_eq!(FrequencyFilter(Some('0')).to_string(), "0");
+    }
+
+    #[test]
+    fn harmonics_non_primitive_offset() {
+        let map = parse(".a....\n......\n...a..\n......\n......\n......");
+        let antinodes = map
+            .antinodes(Resonance::Harmonics(None))
+            .sorted()
+            .collect::<Vec<_>>();
+        assert_eq!(
+            antinodes,
+            vec![
+                Vector2D::new(1, 0),
+                Vector2D::new(2, 1),
+                Vector2D::new(3, 2),
+                Vector2D::new(4, 3),
+                Vector2D::new(5, 4),
+            ]
+        );
+    }
+
+    const EXAMPLE_PAIR: &str = "\
+..........
+..........
+..........
+...a......
+..........
+.....a....
+..........
+..........
+..........
+..........";
+
+    #[test]
+    fn ratio_and_max_harmonics() {
+        let map = parse(EXAMPLE_PAIR);
+        let antinodes = |resonance| map.antinodes(resonance).sorted().collect::<Vec<_>>();
+        assert_eq!(
+            antinodes(Resonance::Ratio(2)),
+            vec![Vector2D::new(1, 1), Vector2D::new(7, 7)]
+        );
+        assert_eq!(
+            antinodes(Resonance::Ratio(3)),
+            vec![Vector2D::new(2, 2), Vector2D::new(6, 6)]
+        );
+        assert_eq!(antinodes(Resonance::Ratio(4)), vec![]);
+        assert_eq!(
+            antinodes(Resonance::Harmonics(Some(1))),
+            vec![
+                Vector2D::new(2, 2),
+                Vector2D::new(3, 3),
+                Vector2D::new(4, 4),
+                Vector2D::new(5, 5),
+                Vector2D::new(6, 6)
+            ]
+        );
+    }
 }
@@ -1,16 +1,30 @@
+use crate::params::{Param, Parameter};
 use aoc_runner_derive::{aoc, aoc_generator};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
 
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
 struct DiskSection {
     file_id: u64,
     start: u64,
     end: u64,
-    moved: bool,
+}
+
+impl DiskSection {
+    fn size(&self) -> u64 {
+        self.end - self.start
+    }
 }
 
 #[derive(Debug, Clone)]
 struct DiskMap {
+    /// The files, sorted by their start.
+    /// A file may be split across multiple sections after compacting.
     sections: Vec<DiskSection>,
+    /// Total number of blocks on the disk.
+    size: u64,
 }
 
 #[aoc_generator(day9)]
@@ -26,54 +40,202 @@ fn parse(input: &str) -> DiskMap {
                 file_id,
                 start: block_index,
                 end: block_index + size,
-                moved: false,
             });
             file_id += 1;
         }
         block_index += size;
         free_space = !free_space;
     }
-    DiskMap { sections }
+    DiskMap {
+        sections,
+        size: block_index,
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+enum CompactionStrategy {
+    /// Move single blocks from the end of the disk to the leftmost free block.
+    Blocks,
+    /// Move whole files to the leftmost free range that fits.
+    FirstFit,
+    /// Move whole files to the smallest free range that fits.
+    BestFit,
+    /// Move whole files to the largest free range that fits.
+    WorstFit,
+    /// Move whole files as far left as possible, without changing their order.
+    Shift,
+}
+
+impl FromStr for CompactionStrategy {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Ok(match s {
+            "blocks" => CompactionStrategy::Blocks,
+            "first-fit" => CompactionStrategy::FirstFit,
+            "best-fit" => CompactionStrategy::BestFit,
+            "worst-fit" => CompactionStrategy::WorstFit,
+            "shift" => CompactionStrategy::Shift,
+            _ => return Err(format!("unknown strategy: {s}")),
+        })
+    }
+}
+
+impl Display for CompactionStrategy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            CompactionStrategy::Blocks => "blocks",
+            CompactionStrategy::FirstFit => "first-fit",
+            CompactionStrategy::BestFit => "best-fit",
+            CompactionStrategy::WorstFit => "worst-fit",
+            CompactionStrategy::Shift => "shift",
+        })
+    }
 }
 
 impl DiskMap {
-    fn compact_blocks(&mut self) -> &mut Self {
-        while let Some((index, free_start, free_amount)) = self.next_free_range(1) {
-            let last_section = self.sections.last_mut().unwrap();
-            let last_file_id = last_section.file_id;
-            let last_size = last_section.end - last_section.start;
-            let new_section = if last_size < free_amount {
-                self.sections.pop().unwrap();
-                DiskSection {
-                    file_id: last_file_id,
-                    start: free_start,
-                    end: free_start + last_size,
-                    moved: true,
+    /// The ranges of free blocks between the sections, as (start, end).
+    fn free_ranges(&self) -> Vec<(u64, u64)> {
+        // Empty files don't split up free space
+        let mut end = 0;
+        let mut result = vec![];
+        for section in self.sections.iter().filter(|section| section.size() > 0) {
+            if end < section.start {
+                result.push((end, section.start));
+            }
+            end = section.end;
+        }
+        result
+    }
+
+    /// Compact the disk, calling `on_move` after every move.
+    fn compact(
+        &mut self,
+        strategy: CompactionStrategy,
+        on_move: &mut dyn FnMut(&DiskMap),
+    ) -> &mut Self {
+        match strategy {
+            CompactionStrategy::Blocks => self.compact_blocks(on_move),
+            CompactionStrategy::Shift => self.shift_files(on_move),
+            _ => self.compact_files(strategy, on_move),
+        };
+        self.sections.retain(|section| section.start < section.end);
+        self.sections.sort_unstable_by_key(|section| section.start);
+        self
+    }
+
+    fn compact_blocks(&mut self, on_move: &mut dyn FnMut(&DiskMap)) {
+        let free_ranges = self.free_ranges();
+        // Moved blocks are added after the original sections
+        let moved_start = self.sections.len();
+        // Files before this index may still have blocks left to move
+        let mut last = self.sections.len();
+        // Fill free ranges from the left with blocks from the last file
+        for (free_start, free_end) in free_ranges {
+            for pos in free_start..free_end {
+                // Empty files have no blocks to move
+                while last > 0 && self.sections[last - 1].size() == 0 {
+                    last -= 1;
+                }
+                let Some(file) = last.checked_sub(1).map(|i| &mut self.sections[i]) else {
+                    break;
+                };
+                if file.start <= pos {
+                    // No more files to the right of this range
+                    break;
                 }
-            } else {
-                last_section.end -= free_amount;
-                DiskSection {
-                    file_id: last_file_id,
-                    start: free_start,
-                    end: free_start + free_amount,
-                    moved: true,
+                let file_id = file.file_id;
+                file.end -= 1;
+                if file.start == file.end {
+                    last -= 1;
                 }
+                match self.sections[moved_start..].last_mut() {
+                    Some(moved) if moved.file_id == file_id && moved.end == pos => moved.end += 1,
+                    _ => self.sections.push(DiskSection {
+                        file_id,
+                        start: pos,
+                        end: pos + 1,
+                    }),
+                }
+                on_move(self);
+            }
+        }
+    }
+
+    fn compact_files(&mut self, strategy: CompactionStrategy, on_move: &mut dyn FnMut(&DiskMap)) {
+        // Start of every free range, grouped by the size of the range.
+        // Free space around empty files can be larger than a single digit.
+        let free_ranges = self.free_ranges();
+        let max_free = free_ranges.iter().map(|(start, end)| end - start).max();
+        let max_free = max_free.unwrap_or(0) as usize;
+        let mut free_by_size = vec![BinaryHeap::<Reverse<u64>>::new(); max_free + 1];
+        for (start, end) in free_ranges {
+            free_by_size[(end - start) as usize].push(Reverse(start));
+        }
+        // Try to move every file once, from right to left
+        for i in (0..self.sections.len()).rev() {
+            let file = &mut self.sections[i];
+            let size = file.size() as usize;
+            if size == 0 {
+                continue;
+            }
+            // Leftmost free range of every size that is large enough, and left of the file
+            let candidates = (size..=max_free).filter_map(|free_size| {
+                let &Reverse(start) = free_by_size[free_size].peek()?;
+                (start < file.start).then_some((free_size, start))
+            });
+            let target = match strategy {
+                CompactionStrategy::FirstFit => candidates.min_by_key(|&(_, start)| start),
+                CompactionStrategy::BestFit => candidates.min_by_key(|&(free_size, _)| free_size),
+                CompactionStrategy::WorstFit => {
+                    candidates.min_by_key(|&(free_size, _)| Reverse(free_size))
+                }
+                _ => unreachable!("not a file strategy: {strategy}"),
             };
-            self.sections.insert(index, new_section);
+            let Some((free_size, free_start)) = target else {
+                continue;
+            };
+            free_by_size[free_size].pop();
+            file.start = free_start;
+            file.end = free_start + size as u64;
+            // Any remaining free space is now a smaller range.
+            // The space freed up by the file is never used again,
+            // since all remaining files are further to the left.
+            if free_size > size {
+                free_by_size[free_size - size].push(Reverse(file.end));
+            }
+            on_move(self);
         }
-        self
     }
 
-    /// Find the next range of free blocks with at least `size` blocks, if any.
-    /// Returns (index, start of range, number of free blocks)
-    fn next_free_range(&self, min_size: u64) -> Option<(usize, u64, u64)> {
-        let mut iter = self.sections.iter().enumerate().peekable();
-        while let (Some((_, curr)), Some((next_index, next))) = (iter.next(), iter.peek()) {
-            if curr.end + min_size <= next.start {
-                return Some((*next_index, curr.end, next.start - curr.end));
+    fn shift_files(&mut self, on_move: &mut dyn FnMut(&DiskMap)) {
+        let mut end = 0;
+        for i in 0..self.sections.len() {
+            let file = &mut self.sections[i];
+            if file.size() == 0 {
+                continue;
             }
+            if file.start > end {
+                let size = file.size();
+                file.start = end;
+                file.end = end + size;
+                on_move(self);
+            }
+            end = self.sections[i].end;
         }
-        None
+    }
+
+    /// Draw every block like in the puzzle description, e.g. `00...111...2...`.
+    fn render(&self) -> String {
+        let mut blocks = vec!['.'; self.size as usize];
+        for section in &self.sections {
+            let c = u32::try_from(section.file_id)
+                .ok()
+                .and_then(|id| char::from_digit(id, 36))
+                .unwrap_or('#');
+            blocks[section.start as usize..section.end as usize].fill(c);
+        }
+        blocks.into_iter().collect()
     }
 
     fn checksum(&self) -> u64 {
@@ -84,46 +246,41 @@ impl DiskMap {
     }
 }
 
-#[aoc(day9, part1)]
-fn part1(input: &DiskMap) -> u64 {
-    input.clone().compact_blocks().checksum()
+/// The layout of the disk before compacting and after every move.
+fn compaction_frames(input: &DiskMap, strategy: CompactionStrategy) -> Vec<String> {
+    let mut frames = vec![input.render()];
+    input
+        .clone()
+        .compact(strategy, &mut |disk_map| frames.push(disk_map.render()));
+    frames
 }
 
-impl DiskMap {
-    fn compact_files(&mut self) -> &mut Self {
-        while let Some((index, file)) = self.next_unmoved_file() {
-            let size = file.end - file.start;
-            if let Some((free_index, free_start, _)) = self.next_free_range(size) {
-                // Must only move to the front
-                if free_index <= index {
-                    // Move the file
-                    let mut file = self.sections.remove(index);
-                    file.start = free_start;
-                    file.end = free_start + size;
-                    file.moved = true;
-                    self.sections.insert(free_index, file);
-                    continue;
-                }
-            }
-            // Not enough free space left for this file, skip it.
-            let file = &mut self.sections[index];
-            file.moved = true;
-        }
-        self
+#[allow(unused)]
+fn print_compaction(input: &DiskMap, strategy: CompactionStrategy) {
+    for frame in compaction_frames(input, strategy) {
+        println!("{frame}");
     }
+}
 
-    fn next_unmoved_file(&self) -> Option<(usize, &DiskSection)> {
-        self.sections
-            .iter()
-            .enumerate()
-            .rev()
-            .find(|(_, section)| !section.moved)
-    }
+const STRATEGY_PART1: Param<CompactionStrategy> =
+    Param::for_part(1, "strategy", CompactionStrategy::Blocks);
+const STRATEGY_PART2: Param<CompactionStrategy> =
+    Param::for_part(2, "strategy", CompactionStrategy::FirstFit);
+
+pub(crate) const PARAMS: &[&dyn Parameter] = &[&STRATEGY_PART1, &STRATEGY_PART2];
+
+fn compacted_checksum(input: &DiskMap, strategy: CompactionStrategy) -> u64 {
+    input.clone().compact(strategy, &mut |_| {}).checksum()
+}
+
+#[aoc(day9, part1)]
+fn part1(input: &DiskMap) -> u64 {
+    compacted_checksum(input, STRATEGY_PART1.get())
 }
 
 #[aoc(day9, part2)]
 fn part2(input: &DiskMap) -> u64 {
-    input.clone().compact_files().checksum()
+    compacted_checksum(input, STRATEGY_PART2.get())
 }
 
 #[cfg(test)]
@@ -141,4 +298,214 @@ mod tests {
     fn part2_example() {
         assert_eq!(part2(&parse(EXAMPLE)), 2858);
     }
+
+    /// Expand the disk map into individual blocks.
+    fn blocks(input: &str) -> Vec<Option<u64>> {
+        let mut blocks = Vec::new();
+        for (i, c) in input.chars().enumerate() {
+            let size = c.to_digit(10).unwrap() as usize;
+            let block = (i % 2 == 0).then_some(i as u64 / 2);
+            blocks.extend(std::iter::repeat_n(block, size));
+        }
+        blocks
+    }
+
+    fn blocks_checksum(blocks: &[Option<u64>]) -> u64 {
+        blocks
+            .iter()
+            .enumerate()
+            .map(|(i, block)| i as u64 * block.unwrap_or(0))
+            .sum()
+    }
+
+    /// Move blocks one at a time, by scanning the expanded disk.
+    fn compact_blocks_naive(input: &str) -> u64 {
+        let mut blocks = blocks(input);
+        let mut left = 0;
+        let mut right = blocks.len();
+        loop {
+            while left < right && blocks[left].is_some() {
+                left += 1;
+            }
+            while left < right && blocks[right - 1].is_none() {
+                right -= 1;
+            }
+            if left + 1 >= right {
+                break;
+            }
+            blocks.swap(left, right - 1);
+        }
+        blocks_checksum(&blocks)
+    }
+
+    /// Move whole files, by scanning the expanded disk for every file.
+    fn compact_files_naive(input: &str) -> u64 {
+        let mut blocks = blocks(input);
+        let max_id = blocks.iter().flatten().copied().max().unwrap_or(0);
+        for id in (0..=max_id).rev() {
+            let Some(start) = blocks.iter().position(|&b| b == Some(id)) else {
+                // Empty file
+                continue;
+            };
+            let size = blocks.iter().filter(|&&b| b == Some(id)).count();
+            let free = (0..start).find(|&i| blocks[i..i + size].iter().all(Option::is_none));
+            if let Some(free) = free {
+                for i in 0..size {
+                    blocks.swap(free + i, start + i);
+                }
+            }
+        }
+        blocks_checksum(&blocks)
+    }
+
+    /// Generate a pseudo-random disk map with the given number of digits.
+    fn random_disk_map(seed: u64, len: usize) -> String {
+        let mut state = seed;
+        (0..len)
+            .map(|i| {
+                state = state
+                    .wrapping_mul(6364136223846793005)
+                    .wrapping_add(1442695040888963407);
+                let digit = (state >> 33) % 10;
+                // Mostly non-empty files, with the occasional empty one
+                let digit = if i % 2 == 0 && digit == 0 && i % 3 != 0 {
+                    1
+                } else {
+                    digit
+                };
+                char::from_digit(digit as u32, 10).unwrap()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn compact_against_naive() {
+        let inputs = (0..20).map(|seed| random_disk_map(seed, 1 + 2 * seed as usize * 7));
+        let empty_files = ["2103", "103", "0312", "12003"].map(String::from);
+        for input in inputs.chain(empty_files) {
+            let disk_map = parse(&input);
+            assert_eq!(
+                compacted_checksum(&disk_map, CompactionStrategy::Blocks),
+                compact_blocks_naive(&input),
+                "blocks: {input}"
+            );
+            assert_eq!(
+                compacted_checksum(&disk_map, CompactionStrategy::FirstFit),
+                compact_files_naive(&input),
+                "files: {input}"
+            );
+        }
+    }
+
+    #[test]
+    fn compact_empty_files() {
+        // Generated maps include empty files
+        assert!((0..20).any(|seed| {
+            let input = random_disk_map(seed, 1 + 2 * seed as usize * 7);
+            input.chars().step_by(2).any(|c| c == '0')
+        }));
+        let disk_map = parse("2103");
+        assert_eq!(
+            compaction_frames(&disk_map, CompactionStrategy::Blocks),
+            vec!["00...."]
+        );
+        assert_eq!(compacted_checksum(&disk_map, CompactionStrategy::Blocks), 0);
+        // An empty file doesn't split the free space around it
+        let disk_map = parse("11011");
+        for strategy in [
+            CompactionStrategy::Blocks,
+            CompactionStrategy::FirstFit,
+            CompactionStrategy::BestFit,
+            CompactionStrategy::WorstFit,
+            CompactionStrategy::Shift,
+        ] {
+            assert_eq!(
+                compaction_frames(&disk_map, strategy),
+                vec!["0..2", "02.."],
+                "{strategy}"
+            );
+        }
+    }
+
+    #[test]
+    fn render_example() {
+        assert_eq!(
+            compaction_frames(&parse("12345"), CompactionStrategy::Blocks),
+            vec![
+                "0..111....22222",
+                "02.111....2222.",
+                "022111....222..",
+                "0221112...22...",
+                "02211122..2....",
+                "022111222......",
+            ]
+        );
+        let input = parse(EXAMPLE);
+        assert_eq!(
+            compaction_frames(&input, CompactionStrategy::FirstFit),
+            vec![
+                "00...111...2...333.44.5555.6666.777.888899",
+                "0099.111...2...333.44.5555.6666.777.8888..",
+                "0099.1117772...333.44.5555.6666.....8888..",
+                "0099.111777244.333....5555.6666.....8888..",
+                "00992111777.44.333....5555.6666.....8888..",
+            ]
+        );
+        assert_eq!(
+            compaction_frames(&input, CompactionStrategy::Blocks)
+                .last()
+                .unwrap(),
+            "0099811188827773336446555566.............."
+        );
+        print_compaction(&input, CompactionStrategy::FirstFit);
+    }
+
+    #[test]
+    fn other_strategies() {
+        let last_frame =
+            |input, strategy| compaction_frames(&parse(input), strategy).pop().unwrap();
+        let first_fit = last_frame(EXAMPLE, CompactionStrategy::FirstFit);
+        assert_eq!(last_frame(EXAMPLE, CompactionStrategy::BestFit), first_fit);
+        assert_eq!(last_frame(EXAMPLE, CompactionStrategy::WorstFit), first_fit);
+        assert_eq!(
+            last_frame(EXAMPLE, CompactionStrategy::Shift),
+            "0011123334455556666777888899.............."
+        );
+        assert_eq!(
+            compaction_frames(&parse("1311121"), CompactionStrategy::BestFit),
+            vec!["0...1.2..3", "0...132...", "02..13....", "021..3...."]
+        );
+        assert_eq!(
+            last_frame("1311121", CompactionStrategy::WorstFit),
+            "0321......"
+        );
+        assert_eq!(
+            last_frame("1311121", CompactionStrategy::FirstFit),
+            "0321......"
+        );
+        assert_eq!(
+            last_frame("1311121", CompactionStrategy::Shift),
+            "0123......"
+        );
+        assert_eq!(last_frame("11131", CompactionStrategy::WorstFit), "01.2...");
+        assert_eq!(last_frame("11131", CompactionStrategy::FirstFit), "021....");
+        assert_eq!(
+            "best-fit"
+                .parse::<CompactionStrategy>()
+                .unwrap()
+                .to_string(),
+            "best-fit"
+        );
+        assert!("next-fit".parse::<CompactionStrategy>().is_err());
+    }
+
+    #[test]
+    fn compact_large() {
+        let input = random_disk_map(9, 100_000);
+        let disk_map = parse(&input);
+        let blocks = compacted_checksum(&disk_map, CompactionStrategy::Blocks);
+        assert_eq!(blocks, compact_blocks_naive(&input));
+        let files = compacted_checksum(&disk_map, CompactionStrategy::FirstFit);
+        assert!(files > 0);
+    }
 }
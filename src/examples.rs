@@ -0,0 +1,92 @@
+//! Example tests, generated from the files in `examples/2024`.
+//!
+//! Every example `dayN.txt` (or `dayN-suffix.txt`) with a sidecar `.answers` file
+//! of the same name gets its own test. The sidecar lists the expected answer for each part,
+//! along with any parameter overrides:
+//!
+//! ```text
+//! # Parameters for all parts
+//! width = 11
+//! height = 7
+//! # Expected answers
+//! part1 = 12
+//! # Parameters for a single part
+//! part1.min_saving = 1
+//! ```
+
+use crate::{params, solutions};
+use aoc_runner::ArcStr;
+use std::collections::HashMap;
+use std::fs;
+
+include!(concat!(env!("OUT_DIR"), "/examples.rs"));
+
+#[derive(Debug, Default)]
+struct Answers {
+    parts: Vec<(u32, String)>,
+    params: HashMap<String, String>,
+}
+
+fn parse_answers(input: &str) -> Answers {
+    let mut answers = Answers::default();
+    for line in input.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (key, value) = line.split_once('=').expect("expected key = value");
+        let (key, value) = (key.trim(), value.trim().to_string());
+        match key.strip_prefix("part").and_then(|part| part.parse().ok()) {
+            Some(part) => answers.parts.push((part, value)),
+            None => {
+                answers.params.insert(key.to_string(), value);
+            }
+        }
+    }
+    answers
+}
+
+fn parse_day(name: &str) -> u32 {
+    let digits = name
+        .strip_prefix("day")
+        .expect("example name must start with 'day'")
+        .split(|c: char| !c.is_ascii_digit())
+        .next()
+        .unwrap();
+    digits.parse().unwrap()
+}
+
+fn check_example(name: &str) {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/examples/2024");
+    let input = fs::read_to_string(format!("{dir}/{name}.txt")).unwrap();
+    let answers = parse_answers(&fs::read_to_string(format!("{dir}/{name}.answers")).unwrap());
+    let day = parse_day(name);
+    assert!(!answers.parts.is_empty(), "no answers for {name}");
+    for (part, expected) in &answers.parts {
+        let solution = solutions::find(day, *part)
+            .unwrap_or_else(|| panic!("no solution for day {day} part {part}"));
+        let overrides = params::for_part(&answers.params, *part);
+        let actual = params::with_overrides(overrides, || {
+            let runner = solution(ArcStr::from(&input)).unwrap();
+            runner.try_run().unwrap().to_string()
+        });
+        assert_eq!(&actual, expected, "{name} part {part}");
+    }
+}
+
+#[test]
+fn parse_answers_example() {
+    let answers = parse_answers("# comment\nsize = 6\npart1 = 22\npart2 = 6,1\npart1.bytes = 12\n");
+    assert_eq!(
+        answers.parts,
+        vec![(1, "22".to_string()), (2, "6,1".to_string())]
+    );
+    assert_eq!(
+        params::for_part(&answers.params, 1),
+        HashMap::from([("size".into(), "6".into()), ("bytes".into(), "12".into())])
+    );
+    assert_eq!(
+        params::for_part(&answers.params, 2),
+        HashMap::from([("size".into(), "6".into())])
+    );
+}
@@ -0,0 +1,189 @@
+//! Loading puzzle inputs at runtime.
+//!
+//! Inputs are personal, so they're not checked in. An optional `SHA256SUMS` manifest
+//! in the input directory (as generated by `sha256sum day*.txt > SHA256SUMS`) is used
+//! to detect input files that have been corrupted or swapped.
+
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub const INPUT_DIR: &str = "input/2024";
+
+const MANIFEST: &str = "SHA256SUMS";
+
+#[derive(Debug)]
+pub enum InputError {
+    Missing {
+        day: u32,
+        path: PathBuf,
+    },
+    Io {
+        path: PathBuf,
+        source: io::Error,
+    },
+    Checksum {
+        day: u32,
+        expected: String,
+        actual: String,
+    },
+}
+
+impl Display for InputError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        match self {
+            InputError::Missing { day, path } => {
+                write!(
+                    f,
+                    "missing input for day {day} (expected {})",
+                    path.display()
+                )
+            }
+            InputError::Io { path, source } => {
+                write!(f, "failed to read {}: {source}", path.display())
+            }
+            InputError::Checksum {
+                day,
+                expected,
+                actual,
+            } => write!(
+                f,
+                "input for day {day} does not match {MANIFEST} (expected {expected}, got {actual})"
+            ),
+        }
+    }
+}
+
+impl Error for InputError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            InputError::Io { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+/// Load the input for the given day, and validate it against the manifest (if any).
+pub fn load(day: u32) -> Result<String, InputError> {
+    load_from(Path::new(INPUT_DIR), day)
+}
+
+fn load_from(dir: &Path, day: u32) -> Result<String, InputError> {
+    let file_name = format!("day{day}.txt");
+    let path = dir.join(&file_name);
+    let input = match fs::read_to_string(&path) {
+        Ok(input) => input,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            return Err(InputError::Missing { day, path });
+        }
+        Err(source) => return Err(InputError::Io { path, source }),
+    };
+    if let Some(expected) = read_manifest(dir)?.remove(&file_name) {
+        let actual = sha256(&input);
+        if actual != expected {
+            return Err(InputError::Checksum {
+                day,
+                expected,
+                actual,
+            });
+        }
+    }
+    Ok(input)
+}
+
+/// Load the input for the given day in a test, or skip the test if there is no input.
+#[cfg(test)]
+pub fn load_or_skip(day: u32) -> Option<String> {
+    match load(day) {
+        Ok(input) => Some(input),
+        Err(e @ InputError::Missing { .. }) => {
+            eprintln!("skipped: {e}");
+            None
+        }
+        Err(e) => panic!("{e}"),
+    }
+}
+
+/// Read the checksums from the manifest, by file name.
+fn read_manifest(dir: &Path) -> Result<HashMap<String, String>, InputError> {
+    let path = dir.join(MANIFEST);
+    let manifest = match fs::read_to_string(&path) {
+        Ok(manifest) => manifest,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(HashMap::new()),
+        Err(source) => return Err(InputError::Io { path, source }),
+    };
+    Ok(manifest
+        .lines()
+        .filter_map(|line| {
+            let (hash, file_name) = line.split_once(char::is_whitespace)?;
+            // `sha256sum` marks files read in binary mode with a `*`
+            let file_name = file_name.trim_start().trim_start_matches('*');
+            Some((file_name.to_string(), hash.to_ascii_lowercase()))
+        })
+        .collect())
+}
+
+fn sha256(input: &str) -> String {
+    Sha256::digest(input)
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("aoc-2024-input-{name}"));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn sha256_example() {
+        assert_eq!(
+            sha256("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn missing_input() {
+        let dir = temp_dir("missing");
+        assert!(matches!(
+            load_from(&dir, 1),
+            Err(InputError::Missing { day: 1, .. })
+        ));
+    }
+
+    #[test]
+    fn without_manifest() {
+        let dir = temp_dir("without-manifest");
+        fs::write(dir.join("day1.txt"), "abc").unwrap();
+        assert_eq!(load_from(&dir, 1).unwrap(), "abc");
+    }
+
+    #[test]
+    fn with_manifest() {
+        let dir = temp_dir("with-manifest");
+        fs::write(dir.join("day1.txt"), "abc").unwrap();
+        fs::write(dir.join("day2.txt"), "abd").unwrap();
+        fs::write(
+            dir.join(MANIFEST),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad  day1.txt\n\
+             ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad *day2.txt\n",
+        )
+        .unwrap();
+        assert_eq!(load_from(&dir, 1).unwrap(), "abc");
+        assert!(matches!(
+            load_from(&dir, 2),
+            Err(InputError::Checksum { day: 2, .. })
+        ));
+    }
+}
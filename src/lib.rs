@@ -23,6 +23,11 @@ mod day6;
 mod day7;
 mod day8;
 mod day9;
+#[cfg(test)]
+mod examples;
+pub mod input;
+pub mod params;
+pub mod solutions;
 mod util;
 
 extern crate aoc_runner;
@@ -1,7 +1,200 @@
 extern crate advent_of_code_2024;
 extern crate aoc_runner;
-extern crate aoc_runner_derive;
 
-use aoc_runner_derive::aoc_main;
+use advent_of_code_2024::solutions::{Solution, StreamingSolution, SOLUTIONS, STREAMING_SOLUTIONS};
+use advent_of_code_2024::{input, params};
+use aoc_runner::ArcStr;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::BufReader;
+use std::time::Instant;
+use std::{env, fs, process};
 
-aoc_main! { lib = advent_of_code_2024 }
+const USAGE: &str = "Usage: advent-of-code-2024 [options]
+
+Options:
+  -d, --day <DAY>           Only run the given day
+  -p, --part <PART>         Only run the given part
+  -i, --input <FILE>        Read the input from the given file (requires --day)
+      --param <NAME=VALUE>  Override a puzzle parameter (e.g. grid_size=6 or part1.blinks=6)
+      --list-params         List the puzzle parameters of each day, with their defaults
+  -h, --help                Print this help";
+
+#[derive(Debug, Default)]
+struct Args {
+    day: Option<u32>,
+    part: Option<u32>,
+    input: Option<String>,
+    params: HashMap<String, String>,
+    list_params: bool,
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args::default();
+    let mut iter = env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        let mut value = |name: &str| iter.next().ok_or(format!("missing value for {name}"));
+        match arg.as_str() {
+            "-d" | "--day" => {
+                let day = value(&arg)?;
+                args.day = Some(day.parse().map_err(|_| format!("invalid day: {day}"))?);
+            }
+            "-p" | "--part" => {
+                let part = value(&arg)?;
+                args.part = Some(part.parse().map_err(|_| format!("invalid part: {part}"))?);
+            }
+            "-i" | "--input" => {
+                args.input = Some(value(&arg)?);
+            }
+            "--param" => {
+                let param = value(&arg)?;
+                let (name, value) = param
+                    .split_once('=')
+                    .ok_or(format!("invalid parameter: {param}"))?;
+                args.params.insert(name.to_string(), value.to_string());
+            }
+            "--list-params" => {
+                args.list_params = true;
+            }
+            "-h" | "--help" => {
+                println!("{USAGE}");
+                process::exit(0);
+            }
+            _ => return Err(format!("unknown argument: {arg}")),
+        }
+    }
+    if args.input.is_some() && args.day.is_none() {
+        return Err("--input requires --day".to_string());
+    }
+    Ok(args)
+}
+
+fn days(args: &Args) -> Vec<u32> {
+    let mut days = SOLUTIONS
+        .iter()
+        .map(|&(day, _, _)| day)
+        .filter(|&day| args.day.is_none_or(|d| d == day))
+        .collect::<Vec<_>>();
+    days.dedup();
+    days
+}
+
+fn list_params(days: &[u32]) {
+    for &day in days {
+        let declared = params::declared(day);
+        if declared.is_empty() {
+            continue;
+        }
+        println!("Day {day}:");
+        for param in declared {
+            match param.part() {
+                Some(part) => println!(
+                    "  {} = {} (part {part})",
+                    param.name(),
+                    param.default_value()
+                ),
+                None => println!("  {} = {}", param.name(), param.default_value()),
+            }
+        }
+    }
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|e| {
+        eprintln!("{e}\n\n{USAGE}");
+        process::exit(2);
+    });
+    let days = days(&args);
+    if args.list_params {
+        list_params(&days);
+        return;
+    }
+    if let Err(e) = params::check_overrides(&days, &args.params) {
+        eprintln!("{e}");
+        process::exit(2);
+    }
+
+    println!("Advent of code 2024");
+
+    for &day in &days {
+        // Stream large inputs from a file, instead of reading them into memory
+        let streaming = STREAMING_SOLUTIONS
+            .iter()
+            .filter(|&&(d, _, _)| d == day)
+            .collect::<Vec<_>>();
+        if let Some(path) = args.input.as_ref().filter(|_| !streaming.is_empty()) {
+            for &&(_, part, solution) in &streaming {
+                if args.part.is_some_and(|p| p != part) {
+                    continue;
+                }
+                params::with_overrides(params::for_part(&args.params, part), || {
+                    run_streaming(day, part, solution, path)
+                });
+            }
+            continue;
+        }
+        let input = match &args.input {
+            Some(path) => {
+                fs::read_to_string(path).map_err(|e| format!("failed to read {path}: {e}"))
+            }
+            None => input::load(day).map_err(|e| e.to_string()),
+        };
+        let input = match input {
+            Ok(input) => ArcStr::from(&input),
+            Err(e) => {
+                eprintln!("Day {day}: {e}\n");
+                continue;
+            }
+        };
+        for &(_, part, solution) in SOLUTIONS.iter().filter(|&&(d, _, _)| d == day) {
+            if args.part.is_some_and(|p| p != part) {
+                continue;
+            }
+            params::with_overrides(params::for_part(&args.params, part), || {
+                run(day, part, solution, input.clone())
+            });
+        }
+    }
+}
+
+fn run(day: u32, part: u32, solution: Solution, input: ArcStr) {
+    let start_time = Instant::now();
+    match solution(input) {
+        Ok(runner) => {
+            let inter_time = Instant::now();
+            match runner.try_run() {
+                Ok(result) => {
+                    let final_time = Instant::now();
+                    println!(
+                        "Day {day} - Part {part}: {result}\n\tgenerator: {:?},\n\trunner: {:?}\n",
+                        inter_time - start_time,
+                        final_time - inter_time
+                    );
+                }
+                Err(e) => eprintln!("Day {day} - Part {part}: FAILED while running:\n{e:#?}\n"),
+            }
+        }
+        Err(e) => eprintln!("Day {day} - Part {part}: FAILED while generating:\n{e:#?}\n"),
+    }
+}
+
+fn run_streaming(day: u32, part: u32, solution: StreamingSolution, path: &str) {
+    let start_time = Instant::now();
+    let file = match File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("Day {day} - Part {part}: failed to read {path}: {e}\n");
+            return;
+        }
+    };
+    match solution(&mut BufReader::new(file)) {
+        Ok(result) => {
+            let final_time = Instant::now();
+            println!(
+                "Day {day} - Part {part}: {result}\n\tstreamed: {:?}\n",
+                final_time - start_time
+            );
+        }
+        Err(e) => eprintln!("Day {day} - Part {part}: FAILED while running:\n{e:#?}\n"),
+    }
+}
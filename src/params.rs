@@ -0,0 +1,211 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::str::FromStr;
+
+thread_local! {
+    static OVERRIDES: RefCell<HashMap<String, String>> = RefCell::new(HashMap::new());
+}
+
+/// A tunable puzzle parameter, such as the size of a grid.
+///
+/// The real puzzle input always uses the default value, but examples (and custom scenarios)
+/// can override it by name.
+pub struct Param<T> {
+    name: &'static str,
+    part: Option<u32>,
+    default: T,
+}
+
+impl<T: FromStr + Display + Clone> Param<T>
+where
+    T::Err: Display,
+{
+    pub const fn new(name: &'static str, default: T) -> Self {
+        Self {
+            name,
+            part: None,
+            default,
+        }
+    }
+
+    /// A parameter whose default only applies to a single part.
+    pub const fn for_part(part: u32, name: &'static str, default: T) -> Self {
+        Self {
+            name,
+            part: Some(part),
+            default,
+        }
+    }
+
+    /// Get the current value, taking any overrides into account.
+    pub fn get(&self) -> T {
+        OVERRIDES.with_borrow(|overrides| match overrides.get(self.name) {
+            Some(value) => value
+                .parse()
+                .unwrap_or_else(|e| panic!("invalid value for parameter {}: {e}", self.name)),
+            None => self.default.clone(),
+        })
+    }
+}
+
+/// A declared parameter, with its type erased.
+pub trait Parameter {
+    fn name(&self) -> &'static str;
+
+    fn part(&self) -> Option<u32>;
+
+    fn default_value(&self) -> String;
+
+    /// Check whether the given value can be used to override this parameter.
+    fn validate(&self, value: &str) -> Result<(), String>;
+}
+
+impl<T: FromStr + Display + Clone> Parameter for Param<T>
+where
+    T::Err: Display,
+{
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn part(&self) -> Option<u32> {
+        self.part
+    }
+
+    fn default_value(&self) -> String {
+        self.default.to_string()
+    }
+
+    fn validate(&self, value: &str) -> Result<(), String> {
+        value.parse::<T>().map(|_| ()).map_err(|e| e.to_string())
+    }
+}
+
+/// All parameters declared by the given day.
+pub fn declared(day: u32) -> &'static [&'static dyn Parameter] {
+    match day {
+        1 => crate::day1::PARAMS,
+        2 => crate::day2::PARAMS,
+        3 => crate::day3::PARAMS,
+        5 => crate::day5::PARAMS,
+        7 => crate::day7::PARAMS,
+        8 => crate::day8::PARAMS,
+        9 => crate::day9::PARAMS,
+        10 => crate::day10::PARAMS,
+        11 => crate::day11::PARAMS,
+        14 => crate::day14::PARAMS,
+        18 => crate::day18::PARAMS,
+        20 => crate::day20::PARAMS,
+        21 => crate::day21::PARAMS,
+        22 => crate::day22::PARAMS,
+        _ => &[],
+    }
+}
+
+/// Check that every override is declared by at least one of the given days,
+/// and that its value is valid for every parameter it applies to.
+pub fn check_overrides(days: &[u32], overrides: &HashMap<String, String>) -> Result<(), String> {
+    for (key, value) in overrides {
+        let (part, name) = match key.split_once('.') {
+            Some((prefix, name)) => {
+                let part = prefix
+                    .strip_prefix("part")
+                    .and_then(|part| part.parse::<u32>().ok())
+                    .ok_or(format!("invalid parameter: {key} (expected partN.{name})"))?;
+                (Some(part), name)
+            }
+            None => (None, key.as_str()),
+        };
+        let mut declared = days
+            .iter()
+            .flat_map(|&day| declared(day))
+            .filter(|param| {
+                param.name() == name
+                    && part.is_none_or(|part| param.part().is_none_or(|p| p == part))
+            })
+            .peekable();
+        if declared.peek().is_none() {
+            return Err(format!("unknown parameter: {key}"));
+        }
+        for param in declared {
+            param
+                .validate(value)
+                .map_err(|e| format!("invalid value for parameter {key}: {e}"))?;
+        }
+    }
+    Ok(())
+}
+
+/// Resolve the overrides for a single part.
+///
+/// An override can be limited to a single part with `partN.name`,
+/// which takes precedence over an override for all parts.
+pub fn for_part(overrides: &HashMap<String, String>, part: u32) -> HashMap<String, String> {
+    let prefix = format!("part{part}.");
+    let mut result = HashMap::new();
+    for (name, value) in overrides {
+        if !name.contains('.') {
+            result.entry(name.clone()).or_insert_with(|| value.clone());
+        } else if let Some(name) = name.strip_prefix(&prefix) {
+            result.insert(name.to_string(), value.clone());
+        }
+    }
+    result
+}
+
+/// Run `f` with the given parameter overrides.
+pub fn with_overrides<R>(overrides: HashMap<String, String>, f: impl FnOnce() -> R) -> R {
+    let previous = OVERRIDES.replace(overrides);
+    let result = f();
+    OVERRIDES.set(previous);
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn check(days: &[u32], overrides: &[(&str, &str)]) -> Result<(), String> {
+        let overrides = overrides
+            .iter()
+            .map(|&(name, value)| (name.to_string(), value.to_string()))
+            .collect();
+        check_overrides(days, &overrides)
+    }
+
+    #[test]
+    fn check_names() {
+        assert_eq!(check(&[18], &[("grid_size", "6")]), Ok(()));
+        assert_eq!(check(&[11], &[("part1.blinks", "6")]), Ok(()));
+        assert_eq!(
+            check(&[11], &[("grid_size", "6")]),
+            Err("unknown parameter: grid_size".to_string())
+        );
+        assert_eq!(
+            check(&[11], &[("part3.blinks", "6")]),
+            Err("unknown parameter: part3.blinks".to_string())
+        );
+        assert_eq!(
+            check(&[11], &[("foo.blinks", "6")]),
+            Err("invalid parameter: foo.blinks (expected partN.blinks)".to_string())
+        );
+    }
+
+    #[test]
+    fn check_values() {
+        assert_eq!(
+            check(&[18], &[("grid_size", "abc")]),
+            Err("invalid value for parameter grid_size: invalid digit found in string".to_string())
+        );
+        assert!(check(&[11], &[("part2.blinks", "-1")]).is_err());
+        assert_eq!(
+            check(&[10], &[("step", "0")]),
+            Err(
+                "invalid value for parameter step: number would be zero for non-zero type"
+                    .to_string()
+            )
+        );
+        assert_eq!(check(&[10], &[("step", "-1")]), Ok(()));
+    }
+}
@@ -0,0 +1,60 @@
+use crate::*;
+use aoc_runner::{ArcStr, Runner};
+use std::error::Error;
+use std::io::Read;
+
+pub type Solution = fn(ArcStr) -> Result<Box<dyn Runner>, Box<dyn Error>>;
+
+macro_rules! solutions {
+    ($($day:literal => [$($part:literal: $solution:ident),+],)+) => {
+        /// All solutions, as `(day, part, solution)`.
+        pub static SOLUTIONS: &[(u32, u32, Solution)] = &[
+            $($(($day, $part, Factory::$solution),)+)+
+        ];
+    };
+}
+
+solutions! {
+    1 => [1: day1_part1, 2: day1_part2],
+    2 => [1: day2_part1, 2: day2_part2],
+    3 => [1: day3_part1, 2: day3_part2],
+    4 => [1: day4_part1, 2: day4_part2],
+    5 => [1: day5_part1, 2: day5_part2],
+    6 => [1: day6_part1, 2: day6_part2],
+    7 => [1: day7_part1, 2: day7_part2],
+    8 => [1: day8_part1, 2: day8_part2],
+    9 => [1: day9_part1, 2: day9_part2],
+    10 => [1: day10_part1, 2: day10_part2],
+    11 => [1: day11_part1, 2: day11_part2],
+    12 => [1: day12_part1, 2: day12_part2],
+    13 => [1: day13_part1, 2: day13_part2],
+    14 => [1: day14_part1, 2: day14_part2],
+    15 => [1: day15_part1, 2: day15_part2],
+    16 => [1: day16_part1, 2: day16_part2],
+    17 => [1: day17_part1, 2: day17_part2],
+    18 => [1: day18_part1, 2: day18_part2],
+    19 => [1: day19_part1, 2: day19_part2],
+    20 => [1: day20_part1, 2: day20_part2],
+    21 => [1: day21_part1, 2: day21_part2],
+    22 => [1: day22_part1, 2: day22_part2],
+    23 => [1: day23_part1, 2: day23_part2],
+    24 => [1: day24_part1, 2: day24_part2],
+    25 => [1: day25_part1],
+}
+
+/// A solution that reads its input as a stream, rather than all at once.
+pub type StreamingSolution = fn(&mut dyn Read) -> Result<String, Box<dyn Error>>;
+
+/// Solutions that can run on inputs too large to fit in memory, as `(day, part, solution)`.
+pub static STREAMING_SOLUTIONS: &[(u32, u32, StreamingSolution)] = &[
+    (3, 1, |input| Ok(day3::stream_part1(input)?.to_string())),
+    (3, 2, |input| Ok(day3::stream_part2(input)?.to_string())),
+];
+
+/// Find the solution for the given day and part.
+pub fn find(day: u32, part: u32) -> Option<Solution> {
+    SOLUTIONS
+        .iter()
+        .find(|&&(d, p, _)| d == day && p == part)
+        .map(|&(_, _, solution)| solution)
+}